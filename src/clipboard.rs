@@ -0,0 +1,30 @@
+use crate::Result;
+use std::io::Write;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// sets the clipboard of the terminal with an OSC 52 escape sequence
+pub(crate) fn copy<W: Write>(writer: &mut W, text: &str) -> Result<()> {
+    write!(writer, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    Ok(())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(BASE64[n >> (18 - 6 * i) & 0x3f] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
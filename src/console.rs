@@ -0,0 +1,50 @@
+use crossterm::{
+    cursor,
+    event::{self, Event},
+    terminal, Result,
+};
+
+use std::time::Duration;
+
+// the terminal apart from the drawing, replaced by a script in tests
+pub(crate) trait Console {
+    fn enable_raw_mode(&mut self) -> Result<()>;
+    fn disable_raw_mode(&mut self) -> Result<()>;
+    // column and row of the cursor
+    fn position(&mut self) -> Result<(u16, u16)>;
+    // columns and rows of the terminal
+    fn size(&mut self) -> Result<(u16, u16)>;
+    // whether an event can be read before timeout
+    fn poll(&mut self, timeout: Duration) -> Result<bool>;
+    // waits for the next event
+    fn read(&mut self) -> Result<Event>;
+}
+
+// the real terminal
+pub(crate) struct Crossterm;
+
+impl Console for Crossterm {
+    fn enable_raw_mode(&mut self) -> Result<()> {
+        terminal::enable_raw_mode()
+    }
+
+    fn disable_raw_mode(&mut self) -> Result<()> {
+        terminal::disable_raw_mode()
+    }
+
+    fn position(&mut self) -> Result<(u16, u16)> {
+        cursor::position()
+    }
+
+    fn size(&mut self) -> Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn poll(&mut self, timeout: Duration) -> Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn read(&mut self) -> Result<Event> {
+        event::read()
+    }
+}
@@ -0,0 +1,70 @@
+/// Separator between the items of an input, see [`split_records`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordDelimiter {
+    /// Each line is an item.
+    Newline,
+    /// Items are separated by NUL characters and may span several lines.
+    Null,
+    /// Items are separated by empty lines and may span several lines.
+    BlankLine,
+}
+
+/// Splits `input` into items, skipping empty ones.
+///
+/// Multi-line items are matched as a whole with newlines treated as spaces and
+/// drawn as their first line, but returned in full.
+pub fn split_records(input: &str, delimiter: RecordDelimiter) -> Vec<&str> {
+    let records: Vec<&str> = match delimiter {
+        RecordDelimiter::Newline => input.lines().collect(),
+        RecordDelimiter::Null => input.split('\0').collect(),
+        RecordDelimiter::BlankLine => input
+            .split("\n\n")
+            .flat_map(|r| r.split("\r\n\r\n"))
+            .collect(),
+    };
+
+    records
+        .into_iter()
+        .map(|r| match delimiter {
+            // more than two newlines in a row leave some around the records
+            RecordDelimiter::BlankLine => r.trim_matches(['\n', '\r']),
+            _ => r.trim_end_matches(['\n', '\r']),
+        })
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// Joins `items` for output, the inverse of [`split_records`], e.g. to print
+/// the selection for another program.
+///
+/// Every item is followed by the delimiter, so an item ends in a newline
+/// (or NUL) even if it's the only one. [`RecordDelimiter::Null`] keeps file
+/// names with spaces and newlines intact. See [`join_separated`] for other
+/// separators, like commas.
+pub fn join_records<S: AsRef<str>>(items: &[S], delimiter: RecordDelimiter) -> String {
+    let terminator = match delimiter {
+        RecordDelimiter::Newline => "\n",
+        RecordDelimiter::Null => "\0",
+        RecordDelimiter::BlankLine => "\n\n",
+    };
+
+    let mut joined = String::new();
+    for item in items {
+        joined.push_str(item.as_ref());
+        joined.push_str(terminator);
+    }
+    joined
+}
+
+/// Joins `items` with `separator` between them, e.g. `","` for a comma
+/// separated list. Unlike [`join_records`] nothing follows the last item.
+pub fn join_separated<S: AsRef<str>>(items: &[S], separator: &str) -> String {
+    let mut joined = String::new();
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            joined.push_str(separator);
+        }
+        joined.push_str(item.as_ref());
+    }
+    joined
+}
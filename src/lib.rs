@@ -1,34 +1,67 @@
+use console::{Console, Crossterm};
+#[cfg(feature = "async")]
+use crossterm::event::EventStream;
 use crossterm::{
-    cursor, event,
-    event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    cursor,
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, MouseButton, MouseEvent, MouseEventKind,
+    },
+    style::{Attribute, ResetColor, SetAttribute},
     terminal,
     terminal::{
         ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen,
     },
     ErrorKind as CrosstermError, ExecutableCommand, QueueableCommand,
 };
+#[cfg(feature = "async")]
+use futures_util::StreamExt;
 use fuzzy_matcher::skim::SkimMatcherV2;
+use unicode_width::UnicodeWidthStr;
 
 use std::{
     borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     fmt,
     fmt::{Display, Formatter},
-    io::{Error as IoError, Write},
-    slice,
-    time::Duration,
+    io::{self, BufRead, Error as IoError, Write},
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod clock;
+mod console;
+mod input;
+mod matcher;
+mod query;
+mod style;
+mod text;
+
+pub use clock::{Clock, MockClock, SystemClock};
+pub use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+pub use crossterm::style::Color;
+pub use input::{join_records, join_separated, split_records, RecordDelimiter};
+use matcher::by_rank;
+pub use matcher::{rank, score_item, CaseMode, MatchMode, Matcher, MatcherConfig};
+pub use query::{parse_query, Term, TermKind};
+pub use style::{ColorChoice, Markers, Style, Theme};
+pub use text::TruncationStyle;
+
 macro_rules! impl_error {
     ($($err:ident),*) => {
         #[derive(Debug)]
         pub enum Error {
-            $($err($err)),*
+            $($err($err),)*
+            /// The picker got into a state it shouldn't be in, a bug in fz.
+            Internal(&'static str),
         }
 
         impl std::error::Error for Error {
             fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
                 match self {
-                    $(Self::$err(e) => Some(e)),*
+                    $(Self::$err(e) => Some(e),)*
+                    Self::Internal(_) => None,
                 }
             }
         }
@@ -36,7 +69,8 @@ macro_rules! impl_error {
         impl Display for Error {
             fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
                 match self {
-                    $(Self::$err(e) => write!(f, "{}", e)),*
+                    $(Self::$err(e) => write!(f, "{}", e),)*
+                    Self::Internal(what) => write!(f, "internal error: {}", what),
                 }
             }
         }
@@ -50,352 +84,4548 @@ macro_rules! impl_error {
 }
 
 pub fn select<'a, W: Write>(writer: W, list: &'a [&str]) -> Result<Cow<'a, [&'a str]>> {
+    // nothing to pick from, don't touch the terminal
+    if list.is_empty() {
+        return Ok(Cow::Borrowed(&[]));
+    }
     Fz::new(writer)?.select(list)
 }
 
+/// Like [`select`], but awaits the user on an async runtime, see
+/// [`Fz::run_async`].
+#[cfg(feature = "async")]
+pub async fn select_async<'a, W: Write>(writer: W, list: &'a [&str]) -> Result<Cow<'a, [&'a str]>> {
+    Fz::new(writer)?.select_async(list).await
+}
+
+/// Like [`select`], but returns a detailed [`Outcome`] including the final
+/// query, e.g. to create a new item when nothing matched, see [`Fz::run`].
+pub fn select_outcome<'a, W: Write>(writer: W, list: &'a [&'a str]) -> Result<Outcome<'a>> {
+    Fz::new(writer)?.run(list)
+}
+
+/// Like [`select`], but returns the positions of the chosen items in `list`.
+pub fn select_indices<W: Write>(writer: W, list: &[&str]) -> Result<Vec<usize>> {
+    Fz::new(writer)?.select_indices(list)
+}
+
+/// Like [`select`], but takes any items viewable as strings, e.g. `&[String]`,
+/// and returns references to the chosen items.
+pub fn select_items<W: Write, S: AsRef<str>>(writer: W, items: &[S]) -> Result<Vec<&S>> {
+    let list: Vec<&str> = items.iter().map(AsRef::as_ref).collect();
+    let indices = select_indices(writer, &list)?;
+    Ok(indices.into_iter().map(|i| &items[i]).collect())
+}
+
+/// Like [`select`], but takes items paired with data of the caller, e.g. the
+/// records the items are made from. Only the strings are matched and drawn,
+/// the chosen items are returned with their data, so equal strings can't be
+/// mixed up like when looking them up afterwards.
+pub fn select_with<'a, W: Write, T>(
+    writer: W,
+    items: &'a [(&'a str, T)],
+) -> Result<Vec<(&'a str, &'a T)>> {
+    let list: Vec<&str> = items.iter().map(|(item, _)| *item).collect();
+    let indices = select_indices(writer, &list)?;
+    Ok(indices
+        .into_iter()
+        .map(|i| (items[i].0, &items[i].1))
+        .collect())
+}
+
+/// Like [`select`], but takes ownership of the items and moves the chosen ones
+/// out, so the result doesn't borrow from anything.
+pub fn select_owned<W: Write>(writer: W, items: Vec<String>) -> Result<Vec<String>> {
+    let list: Vec<&str> = items.iter().map(String::as_str).collect();
+    let indices = select_indices(writer, &list)?;
+    let mut items: Vec<Option<String>> = items.into_iter().map(Some).collect();
+    Ok(indices
+        .into_iter()
+        .filter_map(|i| items[i].take())
+        .collect())
+}
+
+/// Like [`select`], but reads the items line by line from `reader` (e.g. a
+/// locked stdin) until EOF first, skipping empty lines, and returns owned items.
+pub fn select_reader<W: Write, R: BufRead>(writer: W, reader: R) -> Result<Vec<String>> {
+    let mut lines = reader.lines().collect::<std::result::Result<Vec<_>, _>>()?;
+    lines.retain(|line| !line.is_empty());
+    select_owned(writer, lines)
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
+
+// splits an item into its key and value columns, items without the separator
+// are all key
+fn split_columns(item: &str, separator: char) -> (&str, &str) {
+    match item.split_once(separator) {
+        Some((key, value)) => (key.trim_end(), value.trim_start()),
+        None => (item, &item[item.len()..]),
+    }
+}
+
+// the fields of an item at the given positions joined by the delimiter, and
+// the character index in item of each character of the joined fields
+fn select_fields(item: &str, delimiter: char, fields: &[usize]) -> (String, Vec<usize>) {
+    let (mut text, mut positions) = (String::new(), Vec::new());
+    // character index of the current field in item
+    let (mut start, mut first) = (0, true);
+    for (n, field) in item.split(delimiter).enumerate() {
+        let len = field.chars().count();
+        if fields.contains(&n) {
+            // keep the delimiter before every field but the first selected one
+            if !first {
+                text.push(delimiter);
+                positions.push(start - 1);
+            }
+            first = false;
+            text.push_str(field);
+            positions.extend(start..start + len);
+        }
+        start += len + 1;
+    }
+    (text, positions)
+}
+
+// the first line of a multi-line item with the number of lines left out
+fn display_line(item: &str) -> Cow<'_, str> {
+    let mut lines = item.lines();
+    let first = lines.next().unwrap_or("");
+    match lines.count() {
+        0 => Cow::Borrowed(first),
+        more => Cow::Owned(format!("{} [{} more]", first, more)),
+    }
+}
+
+// adds an item to a buffer of the best limit items, the buffer grows up to
+// twice limit before it's cut down, so it must be sorted and truncated once
+// more after the last push
+fn push_best<T>(best: &mut Vec<T>, item: T, limit: usize, compare: impl FnMut(&T, &T) -> Ordering) {
+    best.push(item);
+    if best.len() >= limit.max(1) * 2 {
+        best.select_nth_unstable_by(limit.max(1) - 1, compare);
+        best.truncate(limit);
+    }
+}
 impl_error!(IoError, CrosstermError);
 
-struct Fz<'a, W: Write> {
-    pattern: String,        // pattern written by user
-    matches: Vec<&'a str>,  // items matched by the pattern
-    offset: usize,          // offset of first item shown to user
-    index: usize,           // visible position, upwards from the bottom
-    selected: Vec<&'a str>, // selected items
-    writer: W,              // stdout/stderr
-    width: u16,             // height of terminal
-    height: u16,            // width of terminal
+/// Fuzzy finder, configured before a call to [`Fz::select`].
+///
+/// [`Fz::new`] returns a picker with default options, which are changed by
+/// chaining the other methods taking and returning `self`, e.g.
+/// `Fz::new(stdout())?.prompt("> ").query(initial).multi(false)`.
+///
+/// The picker keeps its state between calls to [`Fz::select`], so it can be
+/// reopened where the user left off.
+pub struct Fz<'a, W: Write> {
+    items: &'a [&'a str],                      // list given to select
+    pattern: String,                           // pattern written by user
+    matches: Vec<usize>,                       // indices of items matched by the pattern
+    scores: Vec<i64>,                          // scores of matches, 0 for the empty pattern
+    offset: usize,                             // offset of first item shown to user
+    index: usize,                              // visible position, upwards from the bottom
+    selected: Selection,                       // indices of selected items
+    writer: Tracked<W>,                        // stdout/stderr
+    flushed_cursor: Option<(u16, u16)>,        // where the cursor was left at the last flush
+    width: u16,                                // width of terminal
+    height: u16,                               // height of the picker
+    screen_height: u16,                        // height of terminal
+    list_rows: Option<u16>,                    // maximum rows of matches on the alternate screen
+    min_query_len: usize,                      // minimum pattern length before matching
+    matcher_config: MatcherConfig,             // options affecting matching
+    matcher: SkimMatcherV2,                    // matcher built from matcher_config
+    custom_matcher: Option<CustomMatcher<'a>>, // replaces matcher if set
+    border: bool,                              // draw a border around the list
+    keep_final_matches: bool,                  // return matches in Outcome
+    vim_mode: bool,                            // modal editing like in vim
+    mode: EditMode,                            // current mode if vim_mode is set
+    map_result: Option<MapResult<'a>>,         // transform for select_mapped
+    keyboard_wrap: bool,                       // wrap around when moving with keys
+    mouse: bool,                               // capture mouse events
+    scroll_wrap: bool,                         // wrap around when scrolling with mouse
+    selection_count: bool,                     // show the number of selected items
+    accept_highlight_key: KeyEvent,            // key accepting only the highlighted item
+    #[cfg(feature = "clipboard")]
+    copy_key: KeyEvent, // key copying the pattern to the clipboard
+    clock: Box<dyn Clock + 'a>,                // source of time
+    console: Box<dyn Console + 'a>,            // raw mode and events of the terminal
+    idle_timeout: Option<Duration>,            // exit after being idle this long
+    inline_preview: Option<InlinePreview<'a>>, // preview under the highlighted row
+    inline_preview_lines: u16,                 // maximum lines of inline preview
+    assume_sorted: bool,                       // items are already sorted
+    pending_chord: Option<(char, Instant)>,    // first key of a chord and when it was pressed
+    truncation_style: TruncationStyle,         // how overflowing items are cut
+    wrap_highlight: bool,                      // always draw the highlighted item in full
+    ansi: bool,                                // items contain escape sequences
+    exact_match_boost: bool,                   // rank items equal to the pattern first
+    info_format: Option<InfoFormat<'a>>,       // custom status next to the pattern
+    print_query: Option<Box<dyn Write + 'a>>,  // receives the pattern on accept
+    message: Option<&'static str>,             // shown instead of status until next event
+    inline: Option<u16>,                       // rows drawn below the cursor instead of a screen
+    top: u16,                                  // first row of the picker on the terminal
+    start: (u16, u16),                         // cursor position to restore in inline mode
+    setup: Setup,                              // terminal setup done so far
+    tiebreak: Tiebreak,                        // orders equally scored items
+    tiebreak_with: Option<TiebreakFn<'a>>,     // custom order overriding tiebreak
+    limit: Option<usize>,                      // maximum number of matches kept
+    min_score: Option<i64>,                    // weaker matches are hidden
+    query_chips: bool,                         // show the parsed query above the prompt
+    header: Vec<String>,                       // lines drawn between the list and the prompt
+    color: ColorChoice,                        // whether styles are drawn
+    styled: bool,                              // color resolved when starting
+    placeholder: Option<String>,               // shown while the pattern is empty
+    accept_best_match: bool,                   // ENTER picks the best match, not the highlight
+    keep_highlight: bool,                      // follow the highlighted item across edits
+    two_column: Option<char>,                  // separator between key and value columns
+    match_key: bool,                           // match only the key of two column items
+    fields: Option<(char, Vec<usize>)>,        // delimiter and the fields matched against
+    multi: bool,                               // allow selecting several items with TAB
+    expanded: bool,                            // highlighted item drawn in full until next event
+    actions: Vec<(KeyEvent, String)>,          // keys applying an action without exiting
+    on_action: Option<OnAction<'a>>,           // called with the action and highlighted item
+    on_change: Option<OnChange<'a>>,           // called when the highlighted item changes
+    highlighted: Option<usize>,                // item last passed to on_change
+    prompt: String,                            // drawn before the pattern
+    theme: Theme,                              // colors of the parts of the picker
+    markers: Markers,                          // characters drawn left of the items
+    highlight_row: bool,                       // draw the highlighted row in reverse video
+    frame: Option<Frame>,                      // the list as last drawn
+    matched_pattern: Option<String>,           // pattern matches were last computed for
+    preview: Option<Preview<'a>>,              // renders the highlighted item beside the list
+    cursor: usize,                             // byte offset of the cursor in pattern
+    layout: Layout,                            // whether rows are mirrored vertically
+    max_selections: Option<usize>,             // maximum number of selected items
+    match_count: bool,                         // show the counts above the prompt
+    grid: bool,                                // pack short items into columns
+    grid_width: u16,                           // widest item, measured when starting
+    scrollbar: bool,                           // show the position of the view on the right
+    show_scores: bool,                         // draw the score after each match
+    auto_accept_single: bool,                  // accept when editing leaves a single match
+}
+
+// time to complete a chord like `gg` in normal mode
+const CHORD_TIMEOUT: Duration = Duration::from_secs(1);
+
+// what was drawn on a row of the list, redrawing skips rows that would be
+// drawn the same way again
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DrawnRow {
+    item: usize,
+    current: bool,
+    selected: bool,
+    highlight: Vec<usize>,
+    score: Option<i64>,
+}
+
+// a writer remembering whether anything was written since the last flush
+struct Tracked<W> {
+    inner: W,
+    dirty: bool,
+}
+
+impl<W: Write> Write for Tracked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.dirty |= !buf.is_empty();
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.dirty = false;
+        self.inner.flush()
+    }
+}
+
+// a running picker, the terminal is restored when dropped unless closed
+// before, so panics and dropped futures don't leave it in raw mode
+struct Session<'f, 'a, W: Write> {
+    fz: &'f mut Fz<'a, W>,
+    open: bool,
+}
+
+impl<W: Write> Session<'_, '_, W> {
+    // restores the terminal, returning errors instead of ignoring them
+    fn close(mut self) -> Result<()> {
+        self.open = false;
+        self.fz.teardown()
+    }
+}
+
+impl<W: Write> Drop for Session<'_, '_, W> {
+    fn drop(&mut self) {
+        if self.open {
+            // nothing to do about errors while unwinding
+            let _ = self.fz.teardown();
+        }
+    }
+}
+
+// indices of selected items in the order they were selected, with a set to
+// look them up while drawing
+#[derive(Clone, Debug, Default)]
+struct Selection {
+    order: Vec<usize>,
+    set: HashSet<usize>,
+}
+
+impl Selection {
+    fn items(&self) -> &[usize] {
+        &self.order
+    }
+
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn contains(&self, item: usize) -> bool {
+        self.set.contains(&item)
+    }
+
+    // returns whether the item wasn't selected yet
+    fn insert(&mut self, item: usize) -> bool {
+        let new = self.set.insert(item);
+        if new {
+            self.order.push(item);
+        }
+        new
+    }
+
+    // returns whether the item was selected
+    fn remove(&mut self, item: usize) -> bool {
+        let found = self.set.remove(&item);
+        if found {
+            self.order.retain(|&s| s != item);
+        }
+        found
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.set.clear();
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(usize) -> bool) {
+        let set = &mut self.set;
+        self.order.retain(|&s| keep(s) || !set.remove(&s));
+    }
+}
+
+impl From<Vec<usize>> for Selection {
+    fn from(items: Vec<usize>) -> Self {
+        let mut selection = Selection::default();
+        for item in items {
+            selection.insert(item);
+        }
+        selection
+    }
+}
+
+// steps of the terminal setup done by start, teardown undoes only these
+#[derive(Clone, Copy, Debug, Default)]
+struct Setup {
+    raw_mode: bool,
+    screen: bool,    // entered the alternate screen or reserved the inline rows
+    line_wrap: bool, // disabled line wrapping
+    mouse: bool,     // enabled mouse capture
+}
+
+// the list as last drawn, only kept while rows can be redrawn one by one
+struct Frame {
+    size: (u16, u16),
+    key_width: usize,
+    rows: Vec<Option<DrawnRow>>, // by visible position
+}
+
+// reason for leaving the event loop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exit {
+    Accept,          // return selected items or the highlighted item
+    AcceptHighlight, // return the highlighted item, ignoring selections
+    Timeout,         // return nothing after being idle
+    Cancel,          // return nothing, the user aborted
+}
+
+type MapResult<'a> = Box<dyn Fn(&str) -> String + 'a>;
+type InlinePreview<'a> = Box<dyn Fn(&str) -> String + 'a>;
+type Preview<'a> = Box<dyn Fn(&str) -> String + 'a>;
+type InfoFormat<'a> = Box<dyn Fn(&InfoContext) -> String + 'a>;
+type TiebreakFn<'a> = Box<dyn Fn(&str, &str) -> Ordering + 'a>;
+type OnAction<'a> = Box<dyn FnMut(&str, &str) + 'a>;
+type OnChange<'a> = Box<dyn FnMut(&str) + 'a>;
+type CustomMatcher<'a> = Box<dyn Matcher + 'a>;
+
+/// Editing modes of [`Fz::vim_mode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditMode {
+    /// Characters are added to the pattern.
+    Insert,
+    /// Characters are commands.
+    Normal,
+}
+
+/// Order of items with equal scores, see [`Fz::tiebreak`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// Alphabetically.
+    #[default]
+    Alpha,
+    /// Shortest first, then alphabetically.
+    Length,
+    /// In the order of the list.
+    Index,
+}
+
+impl Tiebreak {
+    // orders two items with equal scores, Equal if the list order decides
+    pub(crate) fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Tiebreak::Alpha => a.cmp(b),
+            Tiebreak::Length => a.chars().count().cmp(&b.chars().count()).then(a.cmp(b)),
+            Tiebreak::Index => Ordering::Equal,
+        }
+    }
+}
+
+/// Direction of the picker, see [`Fz::layout`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// Prompt on the last row and the best match right above it, the list
+    /// grows upwards.
+    #[default]
+    Default,
+    /// Prompt on the first row and the best match right below it, the list
+    /// grows downwards.
+    Reverse,
+}
+
+/// State of the picker available to [`Fz::info_format`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InfoContext {
+    /// Number of items matched by the pattern.
+    pub matched: usize,
+    /// Number of items in the list.
+    pub total: usize,
+    /// Number of selected items.
+    pub selected: usize,
+    /// Number of selected items not matched by the pattern.
+    pub hidden: usize,
+    /// Current editing mode, `None` unless [`Fz::vim_mode`] is enabled.
+    pub mode: Option<EditMode>,
+    /// Current case sensitivity.
+    pub case: CaseMode,
+}
+
+/// Detailed result of [`Fz::run`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outcome<'a> {
+    /// How the picker was exited.
+    pub status: Status,
+    /// Selected items, or the highlighted item if nothing was selected.
+    pub selected: Vec<&'a str>,
+    /// Positions of [`Outcome::selected`] in the list, telling apart equal
+    /// items.
+    pub indices: Vec<usize>,
+    /// Pattern at the moment of exiting.
+    pub query: String,
+    /// Ranked matches at the moment of accepting, if enabled with
+    /// [`Fz::keep_final_matches`].
+    pub final_matches: Option<Vec<&'a str>>,
+    /// Column and row of the terminal where the highlighted item was drawn when
+    /// accepting, `None` if nothing was highlighted.
+    pub highlight_position: Option<(u16, u16)>,
+    /// Scores of [`Outcome::selected`] against [`Outcome::query`], `None` for
+    /// selected items the final query doesn't match. The empty query scores 0.
+    pub scores: Vec<Option<i64>>,
+    /// Rank of the highlighted item among the matches when exiting, 0 being the
+    /// best match, `None` if nothing matched.
+    pub highlighted_rank: Option<usize>,
+}
+
+impl<'a> Outcome<'a> {
+    /// Iterates over the selected items, borrowed from the list given to
+    /// [`Fz::run`] rather than from the outcome.
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.selected.iter().copied()
+    }
+}
+
+impl<'a> IntoIterator for Outcome<'a> {
+    type Item = &'a str;
+    type IntoIter = std::vec::IntoIter<&'a str>;
+
+    /// Consumes the outcome, yielding the selected items.
+    fn into_iter(self) -> Self::IntoIter {
+        self.selected.into_iter()
+    }
+}
+
+/// How the picker was exited, see [`Outcome::status`].
+///
+/// A timeout or cancelling takes precedence over everything else. Otherwise selections take
+/// precedence over the pattern: if anything was selected the status is
+/// `Accepted` even if the final pattern matches nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Status {
+    /// Items were accepted, [`Outcome::selected`] is not empty.
+    #[default]
+    Accepted,
+    /// Nothing was selected and the pattern matched nothing (or the list was
+    /// empty), [`Outcome::selected`] is empty. The pattern is still available in
+    /// [`Outcome::query`], e.g. for creating a new item.
+    NoMatches,
+    /// The user was idle longer than [`Fz::idle_timeout`], [`Outcome::selected`]
+    /// is empty.
+    TimedOut,
+    /// The user aborted with ESC or CTRL-C, [`Outcome::selected`] is empty.
+    Cancelled,
+}
+
+/// Saved state of a [`Fz`], see [`Fz::snapshot`] and [`Fz::restore`].
+///
+/// Selections are stored as indices into the list given to [`Fz::select`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FzState {
+    pub pattern: String,
+    /// Byte offset of the cursor in [`FzState::pattern`].
+    pub cursor: usize,
+    pub selected: Vec<usize>,
+    pub offset: usize,
+    pub index: usize,
 }
 
 impl<'a, W: Write> Fz<'a, W> {
-    fn new(writer: W) -> Result<Self> {
+    pub fn new(writer: W) -> Result<Self> {
         let (width, height) = terminal::size()?;
-        Ok(Self {
+        Ok(Self::with_size(writer, width, height))
+    }
+
+    /// Like [`Fz::new`], but takes the size of the terminal instead of asking
+    /// the terminal, e.g. when the writer isn't a terminal.
+    ///
+    /// Resizing the terminal and [`Fz::inline`] still replace the size.
+    pub fn with_size(writer: W, width: u16, height: u16) -> Self {
+        Self {
+            items: &[],
             pattern: String::new(),
             matches: Vec::new(),
+            scores: Vec::new(),
             offset: 0,
             index: 0,
-            selected: Vec::new(),
-            writer,
+            selected: Selection::default(),
+            writer: Tracked {
+                inner: writer,
+                dirty: false,
+            },
+            flushed_cursor: None,
             width,
             height,
-        })
+            screen_height: height,
+            list_rows: None,
+            min_query_len: 0,
+            matcher_config: MatcherConfig::default(),
+            matcher: MatcherConfig::default().matcher(),
+            custom_matcher: None,
+            border: false,
+            keep_final_matches: false,
+            vim_mode: false,
+            mode: EditMode::Insert,
+            map_result: None,
+            keyboard_wrap: false,
+            mouse: false,
+            scroll_wrap: false,
+            selection_count: false,
+            accept_highlight_key: KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT),
+            #[cfg(feature = "clipboard")]
+            copy_key: KeyEvent::new(KeyCode::Char('y'), KeyModifiers::ALT),
+            clock: Box::new(SystemClock),
+            console: Box::new(Crossterm),
+            idle_timeout: None,
+            inline_preview: None,
+            inline_preview_lines: 3,
+            assume_sorted: false,
+            pending_chord: None,
+            truncation_style: TruncationStyle::default(),
+            wrap_highlight: false,
+            ansi: false,
+            exact_match_boost: false,
+            info_format: None,
+            print_query: None,
+            message: None,
+            inline: None,
+            top: 0,
+            start: (0, 0),
+            setup: Setup::default(),
+            tiebreak: Tiebreak::default(),
+            tiebreak_with: None,
+            limit: None,
+            min_score: None,
+            query_chips: false,
+            header: Vec::new(),
+            color: ColorChoice::default(),
+            styled: false,
+            placeholder: None,
+            accept_best_match: false,
+            keep_highlight: false,
+            two_column: None,
+            match_key: false,
+            fields: None,
+            multi: true,
+            expanded: false,
+            actions: Vec::new(),
+            on_action: None,
+            on_change: None,
+            highlighted: None,
+            prompt: String::new(),
+            theme: Theme::default(),
+            markers: Markers::default(),
+            highlight_row: false,
+            frame: None,
+            matched_pattern: None,
+            preview: None,
+            cursor: 0,
+            layout: Layout::default(),
+            max_selections: None,
+            match_count: false,
+            grid: false,
+            grid_width: 0,
+            scrollbar: false,
+            show_scores: false,
+            auto_accept_single: false,
+        }
     }
 
-    #[inline]
-    fn max_rows(&self) -> u16 {
-        self.height - 2
+    /// Limits the number of selected items to `max`, further selections are
+    /// ignored. With a limit of 1 selecting an item replaces the previous
+    /// selection instead.
+    pub fn max_selections(mut self, max: usize) -> Self {
+        self.max_selections = Some(max);
+        self
     }
 
-    fn move_cursor(&self) -> cursor::MoveTo {
-        // move cursor to the last line, to the end of pattern
-        cursor::MoveTo(self.pattern.chars().count() as u16, self.height - 1)
+    /// Sets the colors of the picker, the colors of the terminal by default.
+    ///
+    /// Colors are only drawn if enabled by [`Fz::color`].
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
     }
 
-    fn select(mut self, list: &'a [&str]) -> Result<Cow<'a, [&'a str]>> {
-        // initially fill matches with the whole list
-        self.update_matches(list);
+    /// Sets the pointer and selection markers and the indent of the items,
+    /// `>` and `*` in a two column indent by default.
+    pub fn markers(mut self, markers: Markers) -> Self {
+        self.markers = markers;
+        self
+    }
 
-        // setup
-        terminal::enable_raw_mode()?;
-        self.writer
-            .queue(EnterAlternateScreen)?
-            .queue(DisableLineWrap)?;
+    /// Draws the whole highlighted row in reverse video, not just the
+    /// pointer marker. Off by default, only drawn if styles are enabled by
+    /// [`Fz::color`].
+    pub fn highlight_row(mut self, highlight: bool) -> Self {
+        self.highlight_row = highlight;
+        self
+    }
 
-        // initial draw
-        self.redraw()?;
-        self.writer.execute(self.move_cursor())?;
-
-        // event loop
-        loop {
-            // poll if an event is available
-            if let Ok(true) = event::poll(Duration::from_secs(2)) {
-                match event::read() {
-                    // handle resize
-                    Ok(Event::Resize(w, h)) => {
-                        self.width = w;
-                        self.height = h;
-                        self.redraw()?;
-                    }
-                    // return selected items
-                    Ok(Event::Key(
-                        KeyEvent {
-                            code: KeyCode::Enter,
-                            ..
-                        }
-                        | KeyEvent {
-                            code: KeyCode::Char('m'),
-                            modifiers: KeyModifiers::CONTROL,
-                        },
-                    )) => break,
-                    // move up a row
-                    Ok(Event::Key(
-                        KeyEvent {
-                            code: KeyCode::Up, ..
-                        }
-                        | KeyEvent {
-                            code: KeyCode::Char('p'),
-                            modifiers: KeyModifiers::CONTROL,
-                        },
-                    )) => {
-                        if !self.matches.is_empty() {
-                            // don't go up if there are no more matches
-                            if self.offset + self.index < self.matches.len() - 1 {
-                                // clear previous position marker
-                                self.position(false)?;
-
-                                match self.index == self.max_rows() as usize {
-                                    // increment index
-                                    false => self.index += 1,
-                                    // on topmost row -> move the whole view up
-                                    true => {
-                                        self.offset += 1;
-                                        self.redraw()?;
-                                    }
-                                }
-
-                                // draw new position marker
-                                self.position(true)?;
-                            }
-                        }
-                    }
-                    // move down a row
-                    Ok(Event::Key(
-                        KeyEvent {
-                            code: KeyCode::Down,
-                            ..
-                        }
-                        | KeyEvent {
-                            code: KeyCode::Char('n'),
-                            modifiers: KeyModifiers::CONTROL,
-                        },
-                    )) => {
-                        if !self.matches.is_empty() {
-                            // don't go down if already at first match
-                            if self.offset + self.index > 0 {
-                                // clear previous position marker
-                                self.position(false)?;
-
-                                match self.index == 0 {
-                                    // decrement index
-                                    false => self.index -= 1,
-                                    // on bottom row -> move the whole view down
-                                    true => {
-                                        self.offset -= 1;
-                                        self.redraw()?;
-                                    }
-                                }
-
-                                // draw new position marker
-                                self.position(true)?;
-                            }
-                        }
-                    }
-                    // toggle selection
-                    Ok(Event::Key(KeyEvent {
-                        code: KeyCode::Tab, ..
-                    })) => {
-                        if !self.matches.is_empty() {
-                            let current_item = self.matches[self.offset + self.index];
-
-                            // find the index of current_item in selected if it has one
-                            match self.selected.iter().position(|s| *s == current_item) {
-                                // remove the (existing) selection
-                                Some(index) => {
-                                    self.selected.remove(index);
-                                    self.selection(false, self.index as u16)?;
-                                }
-                                // add a new selection
-                                None => {
-                                    self.selected.push(current_item);
-                                    self.selection(true, self.index as u16)?;
-                                }
-                            }
-                        }
-                    }
-                    // erase a character from pattern
-                    Ok(Event::Key(KeyEvent {
-                        code: KeyCode::Backspace,
-                        ..
-                    })) => {
-                        self.pattern.pop();
-
-                        self.update_matches(list);
-                        self.redraw()?;
-                    }
-                    // add a character to pattern (only if no modifiers except SHIFT are pressed)
-                    Ok(Event::Key(KeyEvent {
-                        code: KeyCode::Char(c),
-                        modifiers: km,
-                    })) if !km.intersects(!KeyModifiers::SHIFT) => {
-                        match km {
-                            KeyModifiers::NONE => self.pattern.push(c),
-                            KeyModifiers::SHIFT => self.pattern.push(c.to_ascii_uppercase()),
-                            _ => unreachable!(),
-                        }
+    /// Sets the direction of the picker, [`Layout::Default`] by default.
+    ///
+    /// UP and DOWN (and the mouse wheel) always move the highlight in their
+    /// direction on the screen.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
 
-                        self.update_matches(list);
-                        self.redraw()?;
-                    }
-                    _ => (),
-                }
-            }
+    /// Draws `prompt` before the pattern, e.g. `"> "`. Empty by default.
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
 
-            // move cursor and flush changes
-            self.writer.execute(self.move_cursor())?;
-        }
+    /// Starts with `query` as the pattern, filtering the list from the first
+    /// draw.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.pattern = query.into();
+        self.cursor = self.pattern.len();
+        self
+    }
 
-        // undo the setup
-        self.writer
-            .queue(LeaveAlternateScreen)?
-            .execute(EnableLineWrap)?;
-        terminal::disable_raw_mode()?;
+    /// Shows no matches until the pattern is at least `len` characters long.
+    ///
+    /// Useful for huge lists where scoring and drawing every item for a short
+    /// pattern is slow and rarely helpful.
+    pub fn min_query_len(mut self, len: usize) -> Self {
+        self.min_query_len = len;
+        self
+    }
 
-        // return selected items
-        let selected = match self.selected.is_empty() {
-            true => match self.matches.is_empty() {
-                true => Cow::Borrowed(&[] as &[&str]),
-                false => {
-                    // borrow selected item from list to satisfy borrow checker
-                    let selected_item = list
-                        .iter()
-                        .find(|&i| i == &self.matches[self.offset + self.index])
-                        .unwrap();
-                    Cow::Borrowed(slice::from_ref(selected_item))
-                }
-            },
-            false => Cow::from(self.selected),
-        };
-        Ok(selected)
+    /// Sets the initial case sensitivity of matching, [`CaseMode::Smart`] by default.
+    pub fn case_mode(mut self, mode: CaseMode) -> Self {
+        let mut config = self.matcher_config;
+        config.case_mode = mode;
+        self.set_matcher_config(config);
+        self
     }
 
-    fn redraw(&mut self) -> Result<()> {
-        // clear the whole screen
-        self.writer.queue(terminal::Clear(ClearType::All))?;
+    /// Sets how the pattern is matched, [`MatchMode::Fuzzy`] by default.
+    pub fn match_mode(mut self, mode: MatchMode) -> Self {
+        let mut config = self.matcher_config;
+        config.match_mode = mode;
+        self.set_matcher_config(config);
+        self
+    }
 
-        // can't change during drawing
-        let max_rows = self.max_rows();
+    /// Matches patterns containing `/` segment by segment, see
+    /// [`MatcherConfig::path_mode`]. Off by default.
+    pub fn path_mode(mut self, path_mode: bool) -> Self {
+        let mut config = self.matcher_config;
+        config.path_mode = path_mode;
+        self.set_matcher_config(config);
+        self
+    }
 
-        // draw rows
-        for (i, m) in self
-            .matches
-            .iter()
-            .skip(self.offset) // start iterating matches from offset
-            .take(max_rows as usize + 1) // only print matches that fit on screen
-            .enumerate()
-        {
-            // draw the match
-            self.writer
-                .queue(cursor::MoveTo(2, max_rows - i as u16))?
-                .write_all(m.as_bytes())?;
+    /// Scores items with `matcher` instead of the built-in fuzzy matcher, e.g.
+    /// for ranking by frecency.
+    ///
+    /// The case, match and path modes only affect the built-in matcher, the
+    /// empty pattern still matches every item.
+    pub fn matcher(mut self, matcher: impl Matcher + 'a) -> Self {
+        self.custom_matcher = Some(Box::new(matcher));
+        self
+    }
 
-            // draw selection marker if the match is selected
-            if self.selected.contains(m) {
-                // inlined self.selection to satisfy borrow checker
-                self.writer
-                    .queue(cursor::MoveTo(1, max_rows - i as u16))?
-                    .write_all(b"*")?;
-            }
+    /// Draws a border around the list, off by default.
+    pub fn border(mut self, border: bool) -> Self {
+        self.border = border;
+        self
+    }
 
-            // end overflowing lines with ..
-            if m.chars().count() > self.width as usize - 2
-            // matches start from third column
-            {
-                self.writer
-                    .queue(cursor::MoveTo(self.width - 2, max_rows - i as u16))?
-                    .write_all(b"..")?;
-            }
-        }
+    /// Includes the final ranked matches in [`Outcome::final_matches`], off by
+    /// default to avoid copying them.
+    pub fn keep_final_matches(mut self, keep: bool) -> Self {
+        self.keep_final_matches = keep;
+        self
+    }
 
-        if !self.matches.is_empty() {
-            // draw position marker
-            self.position(true)?;
-        }
+    /// Enables modal editing similar to vim, off by default.
+    ///
+    /// The picker starts in insert mode where characters are added to the
+    /// pattern. ESC switches to normal mode where `j`/`k` move down/up, `gg`
+    /// jumps to the first (best) match, `G` to the last match and `i` or `/`
+    /// switch back to insert mode. ESC in normal mode cancels.
+    pub fn vim_mode(mut self, vim_mode: bool) -> Self {
+        self.vim_mode = vim_mode;
+        self
+    }
 
-        // draw pattern
-        self.writer
-            .queue(cursor::MoveTo(0, self.height - 1))?
-            .write_all(self.pattern.as_bytes())?;
+    /// Sets a transform applied to each item returned by [`Fz::select_mapped`].
+    pub fn map_result(mut self, map: impl Fn(&str) -> String + 'a) -> Self {
+        self.map_result = Some(Box::new(map));
+        self
+    }
 
-        Ok(())
+    /// Wraps around to the other end of the list when moving past either end
+    /// with keys, off by default.
+    pub fn keyboard_wrap(mut self, wrap: bool) -> Self {
+        self.keyboard_wrap = wrap;
+        self
     }
 
-    // shows or hides the position marker for current index
-    fn position(&mut self, show: bool) -> Result<()> {
-        let character = match show {
-            true => b'>',
-            false => b' ',
-        };
+    /// Captures the mouse so the list can be scrolled with the wheel, off by
+    /// default. Clicking a row highlights it, clicking its marker column
+    /// toggles its selection.
+    pub fn mouse(mut self, mouse: bool) -> Self {
+        self.mouse = mouse;
+        self
+    }
 
-        self.writer
-            .queue(cursor::MoveTo(0, self.max_rows() - self.index as u16))?
-            .write_all(&[character])?;
+    /// Wraps around to the other end of the list when scrolling past either
+    /// end with the mouse wheel, off by default.
+    pub fn scroll_wrap(mut self, wrap: bool) -> Self {
+        self.scroll_wrap = wrap;
+        self
+    }
 
-        Ok(())
+    /// Shows the number of selected items next to the pattern, including how
+    /// many of them are hidden by the current pattern. Off by default.
+    pub fn selection_count(mut self, show: bool) -> Self {
+        self.selection_count = show;
+        self
     }
 
-    // shows or hides the selection marker for given row
-    fn selection(&mut self, show: bool, row: u16) -> Result<()> {
-        let character = match show {
-            true => b'*',
-            false => b' ',
+    /// Sets the key accepting only the highlighted item even if other items are
+    /// selected, ALT-ENTER by default.
+    ///
+    /// Some terminals can't tell ALT-ENTER apart from ENTER, CTRL-O
+    /// (`KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL)`) works
+    /// everywhere.
+    pub fn accept_highlight_key(mut self, key: KeyEvent) -> Self {
+        self.accept_highlight_key = key;
+        self
+    }
+
+    /// Sets the key copying the pattern to the clipboard of the terminal,
+    /// ALT-Y by default.
+    #[cfg(feature = "clipboard")]
+    pub fn copy_key(mut self, key: KeyEvent) -> Self {
+        self.copy_key = key;
+        self
+    }
+
+    /// Sets the source of time, [`SystemClock`] by default.
+    pub fn clock(mut self, clock: impl Clock + 'a) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Exits with [`Status::TimedOut`] if no input is received for `timeout`.
+    ///
+    /// The picker never polls: it sleeps until input arrives and reacts to it
+    /// immediately, with a timeout it also wakes up once when the time is up.
+    /// There's no interval to tune between wakeups and responsiveness.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Shows the text returned by `preview` for the highlighted item under its
+    /// row, pushing the rows above it up.
+    pub fn inline_preview(mut self, preview: impl Fn(&str) -> String + 'a) -> Self {
+        self.inline_preview = Some(Box::new(preview));
+        self
+    }
+
+    /// Shows the text returned by `preview` for the highlighted item in a pane
+    /// on the right half of the screen, wrapped to its width.
+    pub fn preview(mut self, preview: impl Fn(&str) -> String + 'a) -> Self {
+        self.preview = Some(Box::new(preview));
+        self
+    }
+
+    /// Sets the maximum number of lines shown by [`Fz::inline_preview`], 3 by
+    /// default.
+    pub fn inline_preview_lines(mut self, lines: u16) -> Self {
+        self.inline_preview_lines = lines;
+        self
+    }
+
+    /// Promises that the list given to [`Fz::select`] is already sorted, so it
+    /// isn't sorted again when the pattern is empty. Off by default.
+    ///
+    /// Saves the initial sort of large, presorted lists. The order of an
+    /// unsorted list is kept as is.
+    pub fn assume_sorted(mut self, sorted: bool) -> Self {
+        self.assume_sorted = sorted;
+        self
+    }
+
+    /// Sets how items wider than the list are cut, [`TruncationStyle::Ellipsis`]
+    /// by default.
+    pub fn truncation_style(mut self, style: TruncationStyle) -> Self {
+        self.truncation_style = style;
+        self
+    }
+
+    /// Always draws the highlighted item in full, wrapped over the rows above
+    /// it (below it in the reverse layout) like ALT-E does until the next key.
+    /// Other items are still cut. Off by default.
+    pub fn wrap_highlight(mut self, wrap: bool) -> Self {
+        self.wrap_highlight = wrap;
+        self
+    }
+
+    /// Draws escape sequences in items (e.g. colors of `ls --color`) instead
+    /// of counting them as text, matching only the text between them. Styles
+    /// set by an item are reset at the end of its row. Off by default.
+    ///
+    /// Items are returned as given, including their escape sequences.
+    pub fn ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    /// Ranks items equal to the pattern first regardless of their score, off by
+    /// default. Case sensitivity follows the [`CaseMode`].
+    pub fn exact_match_boost(mut self, boost: bool) -> Self {
+        self.exact_match_boost = boost;
+        self
+    }
+
+    /// Replaces the status drawn at the end of the pattern line with the text
+    /// returned by `format`, clipped to the width of the terminal.
+    ///
+    /// By default the status shows the editing mode in vim mode, the case mode
+    /// unless it's [`CaseMode::Smart`] and (if enabled by
+    /// [`Fz::selection_count`]) the number of selected items.
+    pub fn info_format(mut self, format: impl Fn(&InfoContext) -> String + 'a) -> Self {
+        self.info_format = Some(Box::new(format));
+        self
+    }
+
+    /// Writes the final pattern followed by a newline to `out` when items are
+    /// accepted.
+    ///
+    /// With the `clipboard` feature the pattern can also be copied to the
+    /// clipboard at any time with ALT-Y (see `Fz::copy_key`).
+    pub fn print_query(mut self, out: impl Write + 'a) -> Self {
+        self.print_query = Some(Box::new(out));
+        self
+    }
+
+    /// Shows at most `rows` rows of matches, shrinking the picker to the
+    /// bottom of the screen (the top with [`Layout::Reverse`]) on tall
+    /// terminals. Unlimited by default, ignored with [`Fz::inline`] which
+    /// sets the height of the whole picker.
+    pub fn list_rows(mut self, rows: u16) -> Self {
+        self.list_rows = Some(rows);
+        self
+    }
+
+    /// Draws the picker in `rows` rows starting at the cursor instead of on the
+    /// alternate screen, like the `--height` option of fzf. The output above
+    /// the picker and the scrollback stay visible. If the cursor isn't at the
+    /// start of a line, the picker starts on the next one to keep the text
+    /// before the cursor (e.g. a prompt).
+    ///
+    /// The terminal is scrolled up if there isn't enough room below the cursor.
+    /// On exit the rows are cleared and the cursor is put back where it was,
+    /// on the same text even if the terminal had to scroll.
+    pub fn inline(mut self, rows: u16) -> Self {
+        self.inline = Some(rows);
+        self
+    }
+
+    /// Sets how items with equal scores (and all items when the pattern is
+    /// empty) are ordered, [`Tiebreak::Alpha`] by default.
+    pub fn tiebreak(mut self, tiebreak: Tiebreak) -> Self {
+        self.tiebreak = tiebreak;
+        self
+    }
+
+    /// Keeps the order of the list when the pattern is empty and between
+    /// items with equal scores, e.g. for a log where the order means
+    /// something. Same as [`Tiebreak::Index`], `false` goes back to the
+    /// default [`Tiebreak::Alpha`].
+    pub fn keep_order(mut self, keep: bool) -> Self {
+        self.tiebreak = match keep {
+            true => Tiebreak::Index,
+            false => Tiebreak::Alpha,
         };
+        self
+    }
 
-        self.writer
-            .queue(cursor::MoveTo(1, self.max_rows() - row))?
-            .write_all(&[character])?;
+    /// Orders items with equal scores (and all items when the pattern is
+    /// empty) with `compare`, overriding [`Fz::tiebreak`].
+    ///
+    /// Items `compare` considers equal keep their order in the list.
+    pub fn tiebreak_with(mut self, compare: impl Fn(&str, &str) -> Ordering + 'a) -> Self {
+        self.tiebreak_with = Some(Box::new(compare));
+        self
+    }
 
-        Ok(())
+    /// Keeps only the `limit` best matches.
+    ///
+    /// Matches are collected into a buffer of at most twice `limit` items that
+    /// is cut down to the best `limit` whenever it fills up, so scoring a huge
+    /// list for a selective pattern (or listing it for the empty pattern)
+    /// doesn't need memory for every match.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
     }
 
-    fn update_matches(&mut self, items: &'a [&str]) {
-        self.matches.clear();
+    /// Hides matches scoring less than `score`, e.g. loose fuzzy matches of
+    /// short patterns. The empty pattern still shows every item.
+    pub fn min_score(mut self, score: i64) -> Self {
+        self.min_score = Some(score);
+        self
+    }
 
-        match self.pattern.is_empty() {
-            // match all items if pattern is empty
-            true => {
-                // add all items and sort them
-                self.matches.extend(items);
-                self.matches.sort_unstable();
-                // there can't be less matches than previously
-                //   -> offset + index will point to an existing item
-            }
-            // fuzzy match items with non-empty pattern
-            false => {
-                let matcher = SkimMatcherV2::default();
-                // items with corresponding scores (for sorting)
-                let mut scored = Vec::new();
+    /// Shows the number of matches out of all items and the number of
+    /// selected items on a row above the prompt, like `42/1000 (3 selected)`.
+    /// Off by default.
+    pub fn match_count(mut self, show: bool) -> Self {
+        self.match_count = show;
+        self
+    }
 
-                for item in items {
-                    if let Some((score, _indices)) = matcher.fuzzy(item, &self.pattern, false) {
-                        scored.push((item, score));
-                    }
-                }
+    /// Draws `lines` between the list and the prompt, e.g. usage hints like
+    /// `TAB: select  ENTER: confirm`. Lines are cut at the width of the
+    /// terminal and take rows from the list. None by default.
+    pub fn header(mut self, lines: Vec<String>) -> Self {
+        self.header = lines;
+        self
+    }
 
-                scored.sort_unstable_by(|(a_item, a_score), (b_item, b_score)| {
-                    match a_score == b_score {
-                        false => a_score.cmp(b_score), // sort by score
-                        true => a_item.cmp(b_item),    // sort by item if scores are equal
-                    }
-                });
+    /// Shows the terms of the pattern as parsed by [`parse_query`] on a row
+    /// above the prompt (and the match count), like `[foo] [!test] ['exact]`,
+    /// alternatives share a chip like `[a | b]`. Off by default.
+    pub fn query_chips(mut self, show: bool) -> Self {
+        self.query_chips = show;
+        self
+    }
 
-                // add sorted matches
-                self.matches.extend(scored.into_iter().map(|(i, _s)| i));
+    /// Sets whether colors and text attributes are drawn, [`ColorChoice::Auto`]
+    /// by default.
+    ///
+    /// The writer can't be checked for being a terminal, use
+    /// [`ColorChoice::Never`] if it may be redirected.
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
 
-                // reset offset so that matches with best scores are visible
-                self.offset = 0;
+    /// Shows dimmed `text` in place of the pattern while it's empty, e.g.
+    /// `Search…`.
+    pub fn placeholder(mut self, text: impl Into<String>) -> Self {
+        self.placeholder = Some(text.into());
+        self
+    }
 
-                match self.matches.is_empty() {
-                    // reset index back to 0 if there are no matches
-                    true => self.index = 0,
-                    false => {
-                        if self.index >= self.matches.len() {
-                            // set index to point to the last item
-                            self.index = self.matches.len() - 1;
-                        }
-                    }
-                }
-            }
-        }
+    /// Makes ENTER accept the best match instead of the highlighted one when
+    /// nothing is selected. Off by default.
+    ///
+    /// Selections are still accepted as usual and the key set with
+    /// [`Fz::accept_highlight_key`] still accepts the highlighted item.
+    pub fn accept_best_match(mut self, best: bool) -> Self {
+        self.accept_best_match = best;
+        self
+    }
+
+    /// Accepts the only match right away when editing the pattern leaves a
+    /// single one, without waiting for ENTER. Never accepts before the
+    /// pattern is edited, even if the list (or the initial [`Fz::query`])
+    /// has a single match. Off by default.
+    ///
+    /// Nothing is accepted while items are selected, so narrowing down after
+    /// selecting doesn't drop the selections, ENTER accepts them as usual.
+    pub fn auto_accept_single(mut self, accept: bool) -> Self {
+        self.auto_accept_single = accept;
+        self
+    }
+
+    /// Keeps the highlighted item highlighted when editing the pattern, as
+    /// long as it still matches, on the same row if possible. Off by default,
+    /// the highlight stays on its row among the best matches instead.
+    pub fn keep_highlight(mut self, keep: bool) -> Self {
+        self.keep_highlight = keep;
+        self
+    }
+
+    /// Draws items as a key and a value column, split at the first `separator`
+    /// (e.g. `':'` for `key: value` lists).
+    ///
+    /// Values are aligned after the widest visible key, but keys take at most
+    /// half of the width. Both columns are truncated independently.
+    pub fn two_column(mut self, separator: char) -> Self {
+        self.two_column = Some(separator);
+        self
+    }
+
+    /// Packs items into as many columns as fit the widest item, filling rows
+    /// from the left. LEFT and RIGHT move between columns instead of moving
+    /// the cursor, and UP and DOWN don't wrap around.
+    ///
+    /// Off by default, ignored with [`Fz::inline_preview`] or
+    /// [`Fz::two_column`] and if only one column fits.
+    pub fn grid(mut self, grid: bool) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// Draws a scrollbar in the rightmost column of the list, showing which
+    /// part of the matches is visible. Items are cut a column earlier.
+    /// Off by default.
+    pub fn scrollbar(mut self, scrollbar: bool) -> Self {
+        self.scrollbar = scrollbar;
+        self
+    }
+
+    /// Draws the score of each match at the right end of its row, e.g. to
+    /// tune patterns or a [`Fz::matcher`]. Items are cut shorter to make room.
+    /// Off by default.
+    pub fn show_scores(mut self, show: bool) -> Self {
+        self.show_scores = show;
+        self
+    }
+
+    /// Matches only the keys of items drawn with [`Fz::two_column`] instead of
+    /// whole items. Off by default.
+    pub fn match_key(mut self, key: bool) -> Self {
+        self.match_key = key;
+        self
+    }
+
+    /// Matches only the given fields of items split at `delimiter` (e.g.
+    /// `'\t'`), counting from 0, while still drawing and returning whole items.
+    ///
+    /// Fields an item doesn't have are skipped, so an item without any of the
+    /// fields matches only the empty pattern. Takes precedence over
+    /// [`Fz::match_key`].
+    pub fn nth(mut self, delimiter: char, fields: &[usize]) -> Self {
+        self.fields = Some((delimiter, fields.to_vec()));
+        self
+    }
+
+    /// Allows selecting several items with TAB, on by default. SHIFT-TAB also
+    /// moves up a row after toggling, for selecting runs of items. CTRL-A
+    /// selects all matches and CTRL-D clears all selections, even hidden ones.
+    ///
+    /// When off these keys do nothing, no selection markers are drawn and ENTER
+    /// always accepts the highlighted item.
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
+
+    /// Binds `key` to the action `name`, passed to the callback set with
+    /// [`Fz::on_action`] together with the highlighted item. The picker stays
+    /// open.
+    ///
+    /// Actions take precedence over the built-in keys.
+    pub fn action(mut self, key: KeyEvent, name: impl Into<String>) -> Self {
+        self.actions.push((key, name.into()));
+        self
+    }
+
+    /// Sets the callback receiving the name of the action and the highlighted
+    /// item when a key bound with [`Fz::action`] is pressed. Nothing happens if
+    /// there are no matches.
+    pub fn on_action(mut self, callback: impl FnMut(&str, &str) + 'a) -> Self {
+        self.on_action = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets a callback receiving the highlighted item whenever a different
+    /// item gets highlighted, including the first one when starting. Redraws
+    /// keeping the highlighted item don't call it.
+    pub fn on_change(mut self, callback: impl FnMut(&str) + 'a) -> Self {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Returns the matches the picker would draw, starting next to the prompt,
+    /// and the position of the highlighted item among them.
+    ///
+    /// Lets hosts rendering the list themselves follow the pagination of the
+    /// picker.
+    pub fn viewport(&self) -> (Vec<&'a str>, usize) {
+        let visible = self
+            .matches
+            .iter()
+            .skip(self.offset)
+            .take(self.page_len())
+            .map(|&m| self.items[m])
+            .collect();
+        (visible, self.index)
+    }
+
+    /// Captures the pattern, selections and position of the picker.
+    pub fn snapshot(&self) -> FzState {
+        FzState {
+            pattern: self.pattern.clone(),
+            cursor: self.cursor,
+            selected: self.selected.items().to_vec(),
+            offset: self.offset,
+            index: self.index,
+        }
+    }
+
+    /// Restores a state captured by [`Fz::snapshot`], used by the next call to
+    /// [`Fz::select`].
+    pub fn restore(&mut self, state: FzState) {
+        self.pattern = state.pattern;
+        // moved to the end when starting if it's not at a character
+        self.cursor = state.cursor;
+        self.selected = state.selected.into();
+        self.offset = state.offset;
+        self.index = state.index;
+    }
+
+    // changes options affecting matching, the matcher (and its cache) is
+    // rebuilt so no state computed under the old options is reused
+    fn set_matcher_config(&mut self, config: MatcherConfig) {
+        self.matcher_config = config;
+        self.matcher = config.matcher();
+        // matches of the old options can't be narrowed down
+        self.matched_pattern = None;
+    }
+
+    #[inline]
+    fn below_min_query_len(&self) -> bool {
+        self.pattern.chars().count() < self.min_query_len
+    }
+
+    // thickness of the border around the list
+    #[inline]
+    fn border_size(&self) -> u16 {
+        self.border as u16
+    }
+
+    // rows reserved for the inline preview
+    #[inline]
+    fn preview_rows(&self) -> u16 {
+        match self.inline_preview {
+            Some(_) => self.inline_preview_lines,
+            None => 0,
+        }
+    }
+
+    // rows between the list and the prompt
+    #[inline]
+    fn info_rows(&self) -> u16 {
+        let header = self.header.len().min(u16::MAX as usize) as u16;
+        header.saturating_add(self.query_chips as u16 + self.match_count as u16)
+    }
+
+    #[inline]
+    fn max_rows(&self) -> u16 {
+        self.height
+            .saturating_sub(2 + 2 * self.border_size() + self.preview_rows() + self.info_rows())
+    }
+
+    // whether at least one row of the list fits above the prompt with a column
+    // for items after the markers, only the prompt is drawn on smaller
+    // terminals
+    #[inline]
+    fn list_fits(&self) -> bool {
+        self.height >= 2 + 2 * self.border_size() + self.preview_rows() + self.info_rows()
+            && self.list_width() > self.markers.indent()
+    }
+
+    // lowest screen row of the list
+    #[inline]
+    fn list_bottom(&self) -> u16 {
+        self.border_size() + self.max_rows() + self.preview_rows()
+    }
+
+    // columns of the list including the border, the left half with a preview
+    #[inline]
+    fn list_columns(&self) -> u16 {
+        match self.preview {
+            Some(_) => self.width / 2,
+            None => self.width,
+        }
+    }
+
+    // width of the list inside the border, without the scrollbar
+    #[inline]
+    fn list_width(&self) -> u16 {
+        self.list_columns()
+            .saturating_sub(2 * self.border_size() + self.scrollbar as u16)
+    }
+
+    // items per row, more than one only in a grid
+    fn grid_columns(&self) -> usize {
+        if !self.grid || self.inline_preview.is_some() || self.two_column.is_some() {
+            return 1;
+        }
+        // cells are separated by a column
+        let cell = self.markers.indent() + self.grid_width + 1;
+        (self.list_width() / cell).max(1) as usize
+    }
+
+    // number of visible positions, the rows times the items per row
+    #[inline]
+    fn page_len(&self) -> usize {
+        (self.max_rows() as usize + 1) * self.grid_columns()
+    }
+
+    // width of the cell of a visible position, the whole list outside a grid
+    fn cell_width(&self) -> u16 {
+        match self.grid_columns() {
+            1 => self.list_width(),
+            columns => self.list_width() / columns as u16,
+        }
+    }
+
+    // screen column where the cell of the given visible position starts
+    fn cell_left(&self, index: usize) -> u16 {
+        self.border_size() + (index % self.grid_columns()) as u16 * self.cell_width()
+    }
+
+    // screen row of the given visible position
+    #[inline]
+    fn row(&self, index: usize) -> u16 {
+        let line = (index / self.grid_columns()) as u16;
+        match index < self.index {
+            true => self.list_bottom() - line,
+            // the highlighted row and rows above it are pushed up by the preview
+            false => self.list_bottom() - self.preview_rows() - line,
+        }
+    }
+
+    // match under the given screen coordinates, the inverse of move_to and row
+    fn clicked(&self, column: u16, row: u16) -> Option<usize> {
+        if !self.list_fits() || column >= self.list_columns() {
+            return None;
+        }
+        let row = row.checked_sub(self.top)?;
+        let row = match self.layout {
+            Layout::Default => row,
+            Layout::Reverse => self.height.checked_sub(1 + row)?,
+        };
+        let visible = (self.matches.len().saturating_sub(self.offset)).min(self.page_len());
+        let width = self.cell_width();
+        (0..visible)
+            .find(|&i| self.row(i) == row && column < self.cell_left(i) + width)
+            .map(|i| self.offset + i)
+    }
+
+    // moves to a row relative to the top of the picker, rows are laid out for
+    // the default layout and mirrored for the reverse layout
+    fn move_to(&self, column: u16, row: u16) -> cursor::MoveTo {
+        let row = match self.layout {
+            Layout::Default => row,
+            Layout::Reverse => self.height.saturating_sub(1 + row),
+        };
+        cursor::MoveTo(column, self.top + row)
+    }
+
+    // shrinks the picker on the alternate screen to list_rows rows of matches,
+    // keeping it at the edge of the screen with the prompt
+    fn fit_height(&mut self) {
+        let screen = self.screen_height;
+        self.height = match self.list_rows {
+            Some(rows) => {
+                let fixed = 1 + 2 * self.border_size() + self.preview_rows() + self.info_rows();
+                rows.max(1).saturating_add(fixed).min(screen)
+            }
+            None => screen,
+        };
+        self.top = match self.layout {
+            Layout::Default => screen - self.height,
+            Layout::Reverse => 0,
+        };
+    }
+
+    // reserves the rows below the cursor, scrolling the terminal if they don't
+    // fit, the start row moves up by as much as the terminal scrolled
+    //
+    // text left of the cursor (e.g. a prompt printed before) is kept by
+    // starting the picker on the next row
+    fn enter_inline(&mut self, rows: u16) -> Result<()> {
+        let (column, row) = self.console.position()?;
+        let (width, height) = self.console.size()?;
+        let below = (column > 0) as u16;
+        self.width = width;
+        self.height = rows.min(height.saturating_sub(below)).max(1);
+        let first = (row + below).min(height);
+        let scroll = self.height.saturating_sub(height - first);
+        if scroll > 0 {
+            self.writer.queue(terminal::ScrollUp(scroll))?;
+        }
+        self.top = first.saturating_sub(scroll);
+        self.start = (column, row.saturating_sub(scroll));
+        Ok(())
+    }
+
+    // clears the picker and restores the cursor to the (compensated) start
+    fn leave_inline(&mut self) -> Result<()> {
+        self.writer
+            .queue(cursor::MoveTo(0, self.top))?
+            .queue(terminal::Clear(ClearType::FromCursorDown))?
+            .queue(cursor::MoveTo(self.start.0, self.start.1))?;
+        Ok(())
+    }
+
+    // queued commands are only flushed when something was drawn or the
+    // cursor has to move, so events changing nothing don't write at all
+    fn flush_changes(&mut self) -> Result<()> {
+        let cursor = self.move_cursor();
+        if self.writer.dirty || self.flushed_cursor != Some((cursor.0, cursor.1)) {
+            self.writer.execute(cursor)?;
+            self.flushed_cursor = Some((cursor.0, cursor.1));
+        }
+        Ok(())
+    }
+
+    fn move_cursor(&self) -> cursor::MoveTo {
+        // move cursor to the last line, to the cursor in pattern in columns
+        // (wide characters take two)
+        let column = self.prompt.width() + self.pattern[..self.cursor].width();
+        let column = column.min(self.width.saturating_sub(1) as usize);
+        self.move_to(column as u16, self.height.saturating_sub(1))
+    }
+
+    /// Lets the user pick items from `list`, returning nothing if the user
+    /// cancelled. Use [`Fz::run`] to tell cancelling apart from accepting.
+    pub fn select(&mut self, list: &'a [&'a str]) -> Result<Cow<'a, [&'a str]>> {
+        Ok(Cow::Owned(self.run(list)?.selected))
+    }
+
+    /// Like [`Fz::select`], but returns the positions of the items in `list`,
+    /// telling apart equal items.
+    pub fn select_indices(&mut self, list: &'a [&'a str]) -> Result<Vec<usize>> {
+        Ok(self.run(list)?.indices)
+    }
+
+    /// Like [`Fz::select`], but returns owned items transformed by the function
+    /// given to [`Fz::map_result`].
+    pub fn select_mapped(&mut self, list: &'a [&'a str]) -> Result<Vec<String>> {
+        let selected = self.run(list)?.selected;
+        Ok(match &self.map_result {
+            Some(map) => selected.into_iter().map(map).collect(),
+            None => selected.into_iter().map(String::from).collect(),
+        })
+    }
+
+    /// Like [`Fz::select`], but returns a detailed [`Outcome`].
+    ///
+    /// An empty `list` returns [`Status::NoMatches`] right away without
+    /// touching the terminal. Failing to read from or draw to the terminal
+    /// returns the error after restoring the terminal, a panic (e.g. of a
+    /// callback) restores it before unwinding further.
+    pub fn run(&mut self, list: &'a [&'a str]) -> Result<Outcome<'a>> {
+        if list.is_empty() {
+            return Ok(self.empty_outcome());
+        }
+        let session = Session {
+            fz: self,
+            open: true,
+        };
+        let exit = session.fz.event_loop(list);
+        // undo the setup even if picking failed
+        let closed = session.close();
+        let exit = exit?;
+        closed?;
+
+        self.finish(list, exit)
+    }
+
+    // sets up the terminal and handles events until picking ends
+    fn event_loop(&mut self, list: &'a [&'a str]) -> Result<Exit> {
+        self.start(list)?;
+
+        let mut last_input = self.clock.now();
+        let exit = loop {
+            // wait for input at most until the idle timeout
+            let mut poll_timeout = None;
+            if let Some(idle_timeout) = self.idle_timeout {
+                let idle = self.clock.now().saturating_duration_since(last_input);
+                match idle_timeout.checked_sub(idle) {
+                    Some(remaining) if !remaining.is_zero() => poll_timeout = Some(remaining),
+                    _ => break Exit::Timeout,
+                }
+            }
+
+            // poll if an event is available, without a timer event::read blocks
+            // until there is one instead of waking up periodically
+            let ready = match poll_timeout {
+                Some(timeout) => self.console.poll(timeout)?,
+                None => true,
+            };
+            if ready {
+                // without a timer reading waits for the event, it arrived when
+                // reading returned
+                let event = self.console.read();
+                last_input = self.clock.now();
+                if let Some(exit) = self.handle_event(event, last_input)? {
+                    break exit;
+                }
+            }
+
+            // move cursor and flush changes, if any
+            self.flush_changes()?;
+        };
+
+        Ok(exit)
+    }
+
+    /// Like [`Fz::select`], but awaits the user on an async runtime, see
+    /// [`Fz::run_async`].
+    #[cfg(feature = "async")]
+    pub async fn select_async(&mut self, list: &'a [&'a str]) -> Result<Cow<'a, [&'a str]>> {
+        Ok(Cow::Owned(self.run_async(list).await?.selected))
+    }
+
+    /// Like [`Fz::run`], but reads events from a crossterm `EventStream`
+    /// instead of blocking, so the picker can be awaited alongside other
+    /// futures, e.g. in `tokio::select!`. Requires the `async` feature.
+    ///
+    /// The stream reads the terminal on a thread of its own, so any executor
+    /// works. The future isn't `Send` and has to be awaited on the task that
+    /// created it. [`Fz::idle_timeout`] is ignored, race the future against a
+    /// timer instead. Dropping the future before it completes restores the
+    /// terminal like cancelling does.
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self, list: &'a [&'a str]) -> Result<Outcome<'a>> {
+        if list.is_empty() {
+            return Ok(self.empty_outcome());
+        }
+        let session = Session {
+            fz: self,
+            open: true,
+        };
+        let exit = session.fz.event_loop_async(list).await;
+        // undo the setup even if picking failed
+        let closed = session.close();
+        let exit = exit?;
+        closed?;
+
+        self.finish(list, exit)
+    }
+
+    // like event_loop, but reading events from an EventStream
+    #[cfg(feature = "async")]
+    async fn event_loop_async(&mut self, list: &'a [&'a str]) -> Result<Exit> {
+        self.start(list)?;
+
+        let mut events = EventStream::new();
+        let exit = loop {
+            // the stream only ends if the terminal can't be read anymore
+            let event = match events.next().await {
+                Some(event) => event,
+                None => break Exit::Cancel,
+            };
+            if let Some(exit) = self.handle_event(event, self.clock.now())? {
+                break exit;
+            }
+
+            // move cursor and flush changes, if any
+            self.flush_changes()?;
+        };
+
+        Ok(exit)
+    }
+
+    // outcome of picking from an empty list
+    fn empty_outcome(&self) -> Outcome<'a> {
+        Outcome {
+            status: Status::NoMatches,
+            query: self.pattern.clone(),
+            final_matches: self.keep_final_matches.then(Vec::new),
+            ..Outcome::default()
+        }
+    }
+
+    // fills the matches, sets up the terminal and draws the picker
+    fn start(&mut self, list: &'a [&'a str]) -> Result<()> {
+        self.items = list;
+        if self.grid {
+            let widest = list
+                .iter()
+                .map(|item| text::visible_width(&display_line(item), self.ansi))
+                .max();
+            self.grid_width = widest.unwrap_or(0).min(u16::MAX as usize) as u16;
+        }
+        self.matched_pattern = None;
+        // the pattern may have been replaced without moving the cursor
+        if !self.pattern.is_char_boundary(self.cursor) {
+            self.cursor = self.pattern.len();
+        }
+        // drop selections that don't point to the new list
+        self.selected.retain(|s| s < list.len());
+
+        // initially fill matches, keeping a (restored) position
+        let (offset, index) = (self.offset, self.index);
+        self.update_matches();
+        self.offset = offset;
+        self.index = index;
+        self.clamp_position();
+
+        // setup
+        self.styled = self.color.enabled();
+        self.frame = None;
+        self.console.enable_raw_mode()?;
+        self.setup.raw_mode = true;
+        match self.inline {
+            Some(rows) => self.enter_inline(rows)?,
+            None => {
+                self.fit_height();
+                self.writer.queue(EnterAlternateScreen)?;
+            }
+        }
+        self.setup.screen = true;
+        self.writer.queue(DisableLineWrap)?;
+        self.setup.line_wrap = true;
+        if self.mouse {
+            self.writer.queue(EnableMouseCapture)?;
+            self.setup.mouse = true;
+        }
+
+        // initial draw
+        self.redraw()?;
+        self.flushed_cursor = None;
+        self.flush_changes()?;
+        self.highlighted = None;
+        self.notify_change();
+        Ok(())
+    }
+
+    // handles an event read at now, returning how to exit if it ends picking
+    fn handle_event(
+        &mut self,
+        event: crossterm::Result<Event>,
+        now: Instant,
+    ) -> Result<Option<Exit>> {
+        // any other event cancels a chord in progress
+        let pending_chord = self
+            .pending_chord
+            .take()
+            .filter(|&(_, t)| now.saturating_duration_since(t) < CHORD_TIMEOUT)
+            .map(|(c, _)| c);
+        // messages are shown until the next event
+        if self.message.take().is_some() {
+            self.draw_prompt()?;
+        }
+        // so is the expanded item
+        let expanded = std::mem::take(&mut self.expanded);
+        let pattern = self.auto_accept_single.then(|| self.pattern.clone());
+
+        let expand_key = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT);
+        if expanded && !matches!(&event, Ok(Event::Key(key)) if *key == expand_key) {
+            self.redraw()?;
+        }
+
+        match event {
+            // handle resize
+            Ok(Event::Resize(w, h)) => {
+                self.width = w;
+                match self.inline {
+                    // keep the picker on the screen
+                    Some(rows) => {
+                        self.height = rows.min(h).max(1);
+                        let top = self.top.min(h.saturating_sub(self.height));
+                        self.start.1 = self.start.1.saturating_sub(self.top - top);
+                        self.top = top;
+                    }
+                    None => {
+                        self.screen_height = h;
+                        self.fit_height();
+                    }
+                }
+                self.redraw()?;
+            }
+            // scroll with mouse wheel
+            Ok(Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                ..
+            })) => self.step(true, self.scroll_wrap)?,
+            Ok(Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                ..
+            })) => self.step(false, self.scroll_wrap)?,
+            // highlight the clicked row, toggle it when clicking the marker
+            Ok(Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            })) => {
+                if let Some(position) = self.clicked(column, row) {
+                    self.jump_to(position)?;
+                    let marker = self.cell_left(self.index) + self.markers.selected_column();
+                    let width = self.markers.selected.width().max(1) as u16;
+                    if self.multi && (marker..marker + width).contains(&column) {
+                        self.toggle_selection()?;
+                    }
+                }
+            }
+            // apply an action to the highlighted item
+            Ok(Event::Key(key)) if self.actions.iter().any(|(k, _)| *k == key) => {
+                self.apply_action(key);
+                self.redraw()?;
+            }
+            // return only the highlighted item
+            Ok(Event::Key(key)) if key == self.accept_highlight_key => {
+                return Ok(Some(Exit::AcceptHighlight))
+            }
+            // copy pattern to clipboard
+            #[cfg(feature = "clipboard")]
+            Ok(Event::Key(key)) if key == self.copy_key => {
+                clipboard::copy(&mut self.writer, &self.pattern)?;
+                self.message = Some("query copied");
+                self.draw_prompt()?;
+            }
+            // return selected items
+            Ok(Event::Key(
+                KeyEvent {
+                    code: KeyCode::Enter,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Char('m'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+            )) => return Ok(Some(Exit::Accept)),
+            // move up a row
+            Ok(Event::Key(
+                KeyEvent {
+                    code: KeyCode::Up, ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Char('p'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+            )) => self.step(true, self.keyboard_wrap)?,
+            // move down a row
+            Ok(Event::Key(
+                KeyEvent {
+                    code: KeyCode::Down,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Char('n'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+            )) => self.step(false, self.keyboard_wrap)?,
+            // toggle selection
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Tab, ..
+            })) if self.multi => self.toggle_selection()?,
+            // toggle selection and move up a row, stopping at the top
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::BackTab,
+                ..
+            })) if self.multi => {
+                self.toggle_selection()?;
+                self.step(true, false)?;
+            }
+            // move a page up or down
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::PageUp,
+                ..
+            })) => self.page(true)?,
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::PageDown,
+                ..
+            })) => self.page(false)?,
+            // jump to the first (best) or the last match
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Home,
+                ..
+            })) if !self.matches.is_empty() => self.jump_to(0)?,
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::End, ..
+            })) if !self.matches.is_empty() => self.jump_to(self.matches.len() - 1)?,
+            // select all matches
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::CONTROL,
+            })) if self.multi => {
+                self.select_all();
+                self.redraw()?;
+            }
+            // clear all selections, including ones hidden by the pattern
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+            })) if self.multi => {
+                self.selected.clear();
+                self.redraw()?;
+            }
+            // jump to the next or previous selected match
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('n'),
+                modifiers: KeyModifiers::ALT,
+            })) => self.jump_to_selected(true)?,
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::ALT,
+            })) => self.jump_to_selected(false)?,
+            // show the highlighted item in full or collapse it again
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::ALT,
+            })) => {
+                self.expanded = !expanded;
+                self.redraw()?;
+            }
+            // cycle case mode
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::ALT,
+            })) => {
+                let mut config = self.matcher_config;
+                config.case_mode = config.case_mode.next();
+                self.set_matcher_config(config);
+
+                self.update_matches();
+                self.redraw()?;
+            }
+            // erase a character from pattern
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            })) => {
+                if let Some(c) = self.pattern[..self.cursor].chars().next_back() {
+                    self.erase_before(self.cursor - c.len_utf8())?;
+                }
+            }
+            // move between the columns of a grid
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Left,
+                ..
+            })) if self.grid_columns() > 1 => self.step_grid(-1)?,
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Right,
+                ..
+            })) if self.grid_columns() > 1 => self.step_grid(1)?,
+            // move the cursor a character left
+            Ok(Event::Key(
+                KeyEvent {
+                    code: KeyCode::Left,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Char('b'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+            )) => {
+                if let Some(c) = self.pattern[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                }
+            }
+            // move the cursor a character right
+            Ok(Event::Key(
+                KeyEvent {
+                    code: KeyCode::Right,
+                    ..
+                }
+                | KeyEvent {
+                    code: KeyCode::Char('f'),
+                    modifiers: KeyModifiers::CONTROL,
+                },
+            )) => {
+                if let Some(c) = self.pattern[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            // erase the word before the cursor
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::CONTROL,
+            })) => {
+                let word = self.pattern[..self.cursor]
+                    .trim_end()
+                    .trim_end_matches(|c: char| !c.is_whitespace());
+                self.erase_before(word.len())?;
+            }
+            // erase the pattern before the cursor
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::CONTROL,
+            })) => self.erase_before(0)?,
+            // return nothing
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+            })) => return Ok(Some(Exit::Cancel)),
+            // ignore unbound characters with modifiers other than SHIFT, all
+            // bound control combinations (e.g. CTRL-M, CTRL-N and CTRL-P) must be
+            // matched before this arm so they never reach the pattern
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(_),
+                modifiers: km,
+            })) if km.intersects(!KeyModifiers::SHIFT) => (),
+            // return nothing, in vim mode only from normal mode
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            })) if !self.vim_mode || self.mode == EditMode::Normal => {
+                return Ok(Some(Exit::Cancel))
+            }
+            // switch to normal mode
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            })) if self.vim_mode => {
+                self.mode = EditMode::Normal;
+                self.redraw()?;
+            }
+            // handle characters as commands in normal mode
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                ..
+            })) if self.mode == EditMode::Normal => match (pending_chord, c) {
+                (Some('g'), 'g') => self.jump_to(0)?,
+                (_, 'g') => self.pending_chord = Some(('g', now)),
+                (_, 'G') if !self.matches.is_empty() => self.jump_to(self.matches.len() - 1)?,
+                (_, 'j') => self.step(false, self.keyboard_wrap)?,
+                (_, 'k') => self.step(true, self.keyboard_wrap)?,
+                (_, 'i') | (_, '/') => {
+                    self.mode = EditMode::Insert;
+                    self.redraw()?;
+                }
+                _ => (),
+            },
+            // add a character to pattern
+            Ok(Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: km,
+            })) => {
+                let c = match km {
+                    KeyModifiers::SHIFT => c.to_ascii_uppercase(),
+                    _ => c,
+                };
+                self.pattern.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+
+                self.update_matches();
+                self.redraw()?;
+            }
+            // the terminal can't be read, reading again would fail as well
+            Err(error) => return Err(error.into()),
+            _ => (),
+        }
+
+        self.notify_change();
+
+        // accept the only match left by editing the pattern, unless there
+        // are selections to accept instead
+        let selecting = self.multi && !self.selected.is_empty();
+        if pattern.map_or(false, |pattern| pattern != self.pattern)
+            && self.matches.len() == 1
+            && !selecting
+        {
+            return Ok(Some(Exit::AcceptHighlight));
+        }
+        Ok(None)
+    }
+
+    // undoes the setup of start
+    //
+    // only the steps that were done are undone, e.g. the screen isn't cleared
+    // if starting failed before the inline rows were reserved
+    fn teardown(&mut self) -> Result<()> {
+        let setup = std::mem::take(&mut self.setup);
+        let restored = self.restore_screen(setup);
+        // leave raw mode even if writing failed
+        if setup.raw_mode {
+            self.console.disable_raw_mode()?;
+        }
+        restored
+    }
+
+    // undoes the drawing side of the setup
+    fn restore_screen(&mut self, setup: Setup) -> Result<()> {
+        if setup.mouse {
+            self.writer.queue(DisableMouseCapture)?;
+        }
+        if setup.screen {
+            match self.inline {
+                Some(_) => self.leave_inline()?,
+                None => {
+                    self.writer.queue(LeaveAlternateScreen)?;
+                }
+            }
+        }
+        if setup.line_wrap {
+            self.writer.queue(EnableLineWrap)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // builds the outcome after the terminal was restored
+    fn finish(&mut self, list: &'a [&'a str], exit: Exit) -> Result<Outcome<'a>> {
+        // return selected items
+        let (status, indices) = match exit {
+            Exit::Timeout => (Status::TimedOut, Vec::new()),
+            Exit::Cancel => (Status::Cancelled, Vec::new()),
+            _ => {
+                if let Some(out) = &mut self.print_query {
+                    writeln!(out, "{}", self.pattern)?;
+                }
+                self.accepted(exit)?
+            }
+        };
+        let final_matches = match self.keep_final_matches {
+            true => Some(self.matches.iter().map(|&m| list[m]).collect()),
+            false => None,
+        };
+        let highlight_position = match (status, self.matches.is_empty()) {
+            (Status::Accepted, false) => {
+                let position = self.move_to(
+                    self.cell_left(self.index) + self.markers.indent(),
+                    self.row(self.index),
+                );
+                Some((position.0, position.1))
+            }
+            _ => None,
+        };
+
+        let scores = match indices.is_empty() {
+            true => Vec::new(),
+            false => {
+                let scored: HashMap<usize, i64> = self
+                    .matches
+                    .iter()
+                    .copied()
+                    .zip(self.scores.iter().copied())
+                    .collect();
+                indices.iter().map(|i| scored.get(i).copied()).collect()
+            }
+        };
+        let highlighted_rank = match self.matches.is_empty() {
+            true => None,
+            false => Some(self.offset + self.index),
+        };
+
+        Ok(Outcome {
+            status,
+            selected: indices.iter().map(|&i| list[i]).collect(),
+            indices,
+            query: self.pattern.clone(),
+            final_matches,
+            highlight_position,
+            scores,
+            highlighted_rank,
+        })
+    }
+
+    // erases the pattern from byte start to the cursor, updating matches only
+    // if it changed
+    fn erase_before(&mut self, start: usize) -> Result<()> {
+        if start == self.cursor {
+            return Ok(());
+        }
+        self.pattern.replace_range(start..self.cursor, "");
+        self.cursor = start;
+
+        self.update_matches();
+        self.redraw()
+    }
+
+    // calls on_change if another item got highlighted since the last call
+    fn notify_change(&mut self) {
+        let current = self.matches.get(self.offset + self.index).copied();
+        if current == self.highlighted {
+            return;
+        }
+        self.highlighted = current;
+        let items = self.items;
+        if let (Some(item), Some(on_change)) = (current, &mut self.on_change) {
+            on_change(items[item]);
+        }
+    }
+
+    // calls on_action with the action bound to key and the highlighted item
+    fn apply_action(&mut self, key: KeyEvent) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let item = self.items[self.matches[self.offset + self.index]];
+        if let (Some((_, name)), Some(on_action)) = (
+            self.actions.iter().find(|(k, _)| *k == key),
+            &mut self.on_action,
+        ) {
+            on_action(name, item);
+        }
+    }
+
+    // items accepted when leaving the event loop
+    fn accepted(&self, exit: Exit) -> Result<(Status, Vec<usize>)> {
+        if exit != Exit::AcceptHighlight && self.multi && !self.selected.is_empty() {
+            return Ok((Status::Accepted, self.selected.items().to_vec()));
+        }
+        if self.matches.is_empty() {
+            return Ok((Status::NoMatches, Vec::new()));
+        }
+        let position = match exit == Exit::Accept && self.accept_best_match {
+            true => 0,
+            false => self.offset + self.index,
+        };
+        let item = self
+            .matches
+            .get(position)
+            .ok_or(Error::Internal("highlighted position past the matches"))?;
+        Ok((Status::Accepted, vec![*item]))
+    }
+
+    fn redraw(&mut self) -> Result<()> {
+        // offset may be stale after resizing or changing matches
+        self.clamp_position();
+
+        // nothing but the prompt fits on a tiny (or narrow) terminal
+        if !self.list_fits() {
+            self.frame = None;
+            self.writer
+                .queue(cursor::MoveTo(0, self.top))?
+                .queue(terminal::Clear(ClearType::FromCursorDown))?;
+            return self.draw_prompt();
+        }
+
+        // rows can only be redrawn one by one if nothing is drawn over them
+        // and they hold a single item
+        let rows = self.page_len();
+        let key_width = self.key_width();
+        let size = (self.width, self.height);
+        let plain = self.inline_preview.is_none()
+            && self.preview.is_none()
+            && !self.draws_expanded()
+            && !self.below_min_query_len()
+            && !self.matches.is_empty()
+            && self.grid_columns() == 1;
+        let mut frame = match self.frame.take() {
+            Some(frame)
+                if plain
+                    && frame.size == size
+                    && frame.key_width == key_width
+                    && frame.rows.len() == rows =>
+            {
+                frame
+            }
+            _ => {
+                // clear the picker from its top row whatever the layout, the
+                // whole screen unless inline
+                self.writer
+                    .queue(cursor::MoveTo(0, self.top))?
+                    .queue(terminal::Clear(ClearType::FromCursorDown))?;
+                Frame {
+                    size,
+                    key_width,
+                    rows: vec![None; rows],
+                }
+            }
+        };
+
+        // draw rows that changed, only matches that fit on screen starting
+        // from offset
+        for i in 0..rows {
+            let drawn = self.matches.get(self.offset + i).map(|&item| DrawnRow {
+                item,
+                current: i == self.index,
+                selected: self.multi && self.selected.contains(item),
+                highlight: self.highlight(item),
+                score: self.show_scores.then(|| self.scores[self.offset + i]),
+            });
+            if drawn == frame.rows[i] {
+                continue;
+            }
+            if frame.rows[i].is_some() {
+                self.writer
+                    .queue(self.move_to(0, self.row(i)))?
+                    .queue(terminal::Clear(ClearType::CurrentLine))?;
+            }
+            if drawn.is_some() {
+                self.draw_row(i, false, key_width)?;
+            }
+            frame.rows[i] = drawn;
+        }
+        if plain {
+            self.frame = Some(frame);
+        }
+
+        if !self.matches.is_empty() {
+            // draw position marker
+            self.position(true)?;
+
+            // draw preview of the highlighted item
+            if let Some(preview) = &self.inline_preview {
+                let text = preview(self.items[self.matches[self.offset + self.index]]);
+                let highlight = self.row(self.index);
+                let left = self.border_size() + self.markers.indent() + 2;
+                let width = self.list_width().saturating_sub(self.markers.indent() + 2) as usize;
+
+                let lines: Vec<&str> = text.lines().take(self.preview_rows() as usize).collect();
+                for (i, line) in lines.iter().enumerate() {
+                    // lines follow the highlight downwards on the screen
+                    let row = match self.layout {
+                        Layout::Default => highlight + 1 + i as u16,
+                        Layout::Reverse => highlight + (lines.len() - i) as u16,
+                    };
+                    let (line, _) = text::split_at_width(line, width);
+                    self.writer
+                        .queue(self.move_to(left, row))?
+                        .write_all(line.as_bytes())?;
+                }
+            }
+
+            if self.draws_expanded() {
+                self.draw_expanded()?;
+            }
+        }
+
+        if self.scrollbar {
+            self.draw_scrollbar()?;
+        }
+
+        // tell the user why nothing is shown
+        let hint = match self.below_min_query_len() {
+            true => Some(format!("type {} characters to search", self.min_query_len)),
+            false if self.matches.is_empty() => Some(String::from("no matches")),
+            false => None,
+        };
+        if let Some(hint) = hint {
+            let (hint, _) = text::split_at_width(
+                &hint,
+                self.list_width().saturating_sub(self.markers.indent()) as usize,
+            );
+
+            self.writer
+                .queue(self.move_to(self.border_size() + self.markers.indent(), self.row(0)))?
+                .write_all(hint.as_bytes())?;
+        }
+
+        // draw border last, overwriting the ends of overflowing matches
+        if self.border {
+            self.draw_border()?;
+        }
+
+        if self.preview.is_some() {
+            self.draw_preview()?;
+        }
+
+        if !self.header.is_empty() {
+            self.draw_header()?;
+        }
+
+        if self.query_chips {
+            self.draw_chips()?;
+        }
+
+        self.draw_prompt()
+    }
+
+    // width of the key column, aligned across visible rows
+    fn key_width(&self) -> usize {
+        match self.two_column {
+            Some(separator) => self
+                .matches
+                .iter()
+                .skip(self.offset)
+                .take(self.page_len())
+                .map(|&m| {
+                    let line = display_line(self.items[m]);
+                    text::visible_width(split_columns(&line, separator).0, self.ansi)
+                })
+                .max()
+                .unwrap_or(0)
+                .min(self.list_width().saturating_sub(self.markers.indent()) as usize / 2),
+            None => 0,
+        }
+    }
+
+    // draws the match at the given visible position with its selection marker,
+    // in the colors of the highlighted row if current
+    fn draw_row(&mut self, i: usize, current: bool, key_width: usize) -> Result<()> {
+        let item = self.matches[self.offset + i];
+        let m = display_line(self.items[item]);
+        let row = self.row(i);
+        let left = self.cell_left(i);
+        let indent = self.markers.indent();
+        // the score takes the right end of the cell if there's room for it
+        let score = Some(format!(" (score: {})", self.scores[self.offset + i]))
+            .filter(|score| self.show_scores && self.cell_width() > indent + score.len() as u16);
+        let score_width = score.as_ref().map_or(0, |score| score.len() as u16);
+        let list_width = self.cell_width() - score_width;
+        let highlight = self.highlight(item);
+        let colors = match (self.styled, current) {
+            (false, _) => (Style::default(), Style::default()),
+            (true, false) => (self.theme.normal, self.theme.matched),
+            (true, true) => (self.theme.current, self.theme.matched),
+        };
+        // fading needs a text attribute
+        let truncation_style = match (self.truncation_style, self.styled) {
+            (TruncationStyle::Fade, false) => TruncationStyle::Clip,
+            (style, _) => style,
+        };
+        let truncation = (truncation_style, self.ansi);
+
+        // draw the match after the markers
+        self.writer.queue(self.move_to(left + indent, row))?;
+        match self.two_column {
+            Some(separator) => {
+                let (key, value) = split_columns(&m, separator);
+                // character index where the value starts in the item
+                let value_start = text::visible_chars(
+                    &m[..value.as_ptr() as usize - m.as_ptr() as usize],
+                    self.ansi,
+                )
+                .count();
+                let value_highlight: Vec<usize> = highlight
+                    .iter()
+                    .filter_map(|&h| h.checked_sub(value_start))
+                    .collect();
+                text::write_truncated(
+                    &mut self.writer,
+                    key,
+                    key_width,
+                    truncation,
+                    &highlight,
+                    colors,
+                )?;
+                // values start after the key column and a space
+                self.writer
+                    .queue(self.move_to(left + indent + 1 + key_width as u16, row))?;
+                text::write_truncated(
+                    &mut self.writer,
+                    value,
+                    (list_width as usize).saturating_sub(indent as usize + 1 + key_width),
+                    truncation,
+                    &value_highlight,
+                    colors,
+                )?;
+            }
+            None => text::write_truncated(
+                &mut self.writer,
+                &m,
+                list_width.saturating_sub(indent) as usize,
+                truncation,
+                &highlight,
+                colors,
+            )?,
+        }
+
+        if let Some(score) = score {
+            self.writer.queue(self.move_to(left + list_width, row))?;
+            self.write_styled(score.as_bytes(), colors.0)?;
+        }
+
+        // draw selection marker if the match is selected, reversed with the
+        // rest of the row if current
+        if self.multi && self.selected.contains(item) {
+            let marker = self.markers.selected.clone();
+            self.writer
+                .queue(self.move_to(left + self.markers.selected_column(), row))?;
+            self.write_styled(marker.as_bytes(), self.theme.marker)?;
+        }
+
+        Ok(())
+    }
+
+    // character indices of the first line of an item matched by the pattern,
+    // nothing if styles are disabled
+    fn highlight(&self, item: usize) -> Vec<usize> {
+        if !self.styled || self.pattern.is_empty() {
+            return Vec::new();
+        }
+        let item = match self.ansi {
+            true => text::strip_ansi(self.items[item]),
+            false => Cow::Borrowed(self.items[item]),
+        };
+        let item = item.as_ref();
+        // matched fields and the positions of their characters in item
+        let (text, positions) = match (&self.fields, self.two_column.filter(|_| self.match_key)) {
+            (Some((delimiter, fields)), _) => {
+                let (text, positions) = select_fields(item, *delimiter, fields);
+                (Cow::Owned(text), Some(positions))
+            }
+            (None, Some(separator)) => (Cow::Borrowed(split_columns(item, separator).0), None),
+            (None, None) => (Cow::Borrowed(item), None),
+        };
+        let first_line = item.lines().next().unwrap_or("").chars().count();
+
+        let indices = match &self.custom_matcher {
+            Some(custom) => Some(custom.indices(&text, &self.pattern)),
+            None => self
+                .matcher_config
+                .score(&self.matcher, &text, &self.pattern, true)
+                .map(|(_score, indices)| indices),
+        };
+        match indices {
+            Some(indices) => indices
+                .into_iter()
+                .map(|i| positions.as_ref().map_or(i, |p| p[i]))
+                .filter(|&i| i < first_line)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // whether the highlighted item is drawn in full
+    #[inline]
+    fn draws_expanded(&self) -> bool {
+        self.expanded || self.wrap_highlight
+    }
+
+    // draws the whole highlighted item wrapped over the rows above it, ending
+    // on the highlighted row
+    fn draw_expanded(&mut self) -> Result<()> {
+        let item = self.items[self.matches[self.offset + self.index]];
+        // escape sequences would be cut when wrapping
+        let item = match self.ansi {
+            true => text::strip_ansi(item),
+            false => Cow::Borrowed(item),
+        };
+        let indent = self.markers.indent();
+        let width = self.list_width().saturating_sub(indent) as usize;
+        let bottom = self.row(self.index);
+        let left = self.border_size();
+
+        let lines = text::wrap(&item, width);
+        let fit = bottom.saturating_sub(self.border_size()) as usize + 1;
+        // lines grow away from the prompt, keep the ones next to the highlight
+        let lines = match self.layout {
+            Layout::Default => &lines[lines.len().saturating_sub(fit)..],
+            Layout::Reverse => &lines[..lines.len().min(fit)],
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            let row = match self.layout {
+                Layout::Default => bottom + 1 + i as u16 - lines.len() as u16,
+                Layout::Reverse => bottom - i as u16,
+            };
+            // the highlighted row keeps its pointer, the others are indented
+            // past the markers of the rows drawn below
+            if row == bottom {
+                self.writer.queue(self.move_to(left + indent, row))?;
+            } else {
+                self.writer.queue(self.move_to(left, row))?;
+                write!(self.writer, "{:indent$}", "", indent = indent as usize)?;
+            }
+            // pad to cover the rows drawn below
+            let padding = width.saturating_sub(line.width());
+            write!(self.writer, "{}{:padding$}", line, "", padding = padding)?;
+        }
+
+        Ok(())
+    }
+
+    // draws the preview pane right of the list, separated by a line
+    fn draw_preview(&mut self) -> Result<()> {
+        let left = self.list_columns();
+        let width = self.width.saturating_sub(left + 2) as usize;
+        // the pane spans the rows of the list and its border
+        let bottom = self.list_bottom() + self.border_size();
+
+        let text = match (&self.preview, self.matches.get(self.offset + self.index)) {
+            (Some(preview), Some(&item)) => preview(self.items[item]),
+            _ => String::new(),
+        };
+        let lines = text::wrap(&text, width);
+
+        for i in 0..=bottom {
+            // lines go down the screen in both layouts
+            let row = match self.layout {
+                Layout::Default => i,
+                Layout::Reverse => bottom - i,
+            };
+            self.writer
+                .queue(self.move_to(left, row))?
+                .write_all("│ ".as_bytes())?;
+            if let Some(line) = lines.get(i as usize) {
+                self.writer.write_all(line.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // draws the terms of the pattern above the prompt
+    fn draw_chips(&mut self) -> Result<()> {
+        let chips: Vec<String> = parse_query(&self.pattern)
+            .iter()
+            .map(|group| {
+                let alternatives: Vec<String> = group.iter().map(Term::to_string).collect();
+                format!("[{}]", alternatives.join(" | "))
+            })
+            .collect();
+        let chips = chips.join(" ");
+        let (chips, _) = text::split_at_width(&chips, self.width as usize);
+
+        self.writer
+            .queue(self.move_to(0, self.height.saturating_sub(2 + self.match_count as u16)))?
+            .queue(terminal::Clear(ClearType::CurrentLine))?
+            .write_all(chips.as_bytes())?;
+        Ok(())
+    }
+
+    // draws the header between the list and the other rows above the prompt,
+    // the lines keep their order in the reverse layout
+    fn draw_header(&mut self) -> Result<()> {
+        let below = 1 + self.query_chips as u16 + self.match_count as u16;
+        let bottom = self.height.saturating_sub(below);
+        let lines = self.header.len();
+        for i in 0..lines {
+            let row = match self.layout {
+                Layout::Default => bottom.saturating_sub((lines - i) as u16),
+                Layout::Reverse => bottom.saturating_sub(1 + i as u16),
+            };
+            let (line, _) = text::split_at_width(&self.header[i], self.width as usize);
+            self.writer
+                .queue(self.move_to(0, row))?
+                .queue(terminal::Clear(ClearType::CurrentLine))?
+                .write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // draws the number of matches, items and selections above the prompt
+    fn draw_match_count(&mut self) -> Result<()> {
+        let info = self.info_context();
+        let mut count = format!("{}/{}", info.matched, info.total);
+        if info.selected > 0 {
+            count.push_str(&format!(" ({} selected)", info.selected));
+        }
+        let (count, _) = text::split_at_width(&count, self.width as usize);
+
+        self.writer
+            .queue(self.move_to(0, self.height.saturating_sub(2)))?
+            .queue(terminal::Clear(ClearType::CurrentLine))?
+            .write_all(count.as_bytes())?;
+        Ok(())
+    }
+
+    // draws the pattern and status on the last line
+    fn draw_prompt(&mut self) -> Result<()> {
+        // draw prompt and pattern, cut at the edge so nothing wraps
+        let line = format!("{}{}", self.prompt, self.pattern);
+        let (line, _) = text::split_at_width(&line, self.width as usize);
+        self.writer
+            .queue(self.move_to(0, self.height.saturating_sub(1)))?
+            .queue(terminal::Clear(ClearType::CurrentLine))?
+            .write_all(line.as_bytes())?;
+
+        // draw placeholder after the cursor, it's never part of the pattern
+        if let (true, Some(placeholder)) = (self.pattern.is_empty(), &self.placeholder) {
+            let width = (self.width as usize).saturating_sub(line.width());
+            let (placeholder, _) = text::split_at_width(placeholder, width);
+            if self.styled {
+                self.writer.queue(SetAttribute(Attribute::Dim))?;
+            }
+            self.writer.write_all(placeholder.as_bytes())?;
+            if self.styled {
+                self.writer.queue(SetAttribute(Attribute::Reset))?;
+            }
+        }
+
+        // draw status to the end of the pattern line if it fits
+        let status = self.status();
+        let (status, _) = text::split_at_width(&status, self.width as usize);
+        let column = (self.width as usize).saturating_sub(status.width());
+        if !status.is_empty() && self.prompt.width() + self.pattern.width() < column {
+            self.writer
+                .queue(self.move_to(column as u16, self.height.saturating_sub(1)))?
+                .write_all(status.as_bytes())?;
+        }
+
+        // the counts change with the prompt
+        if self.match_count && self.list_fits() {
+            self.draw_match_count()?;
+        }
+
+        Ok(())
+    }
+
+    fn info_context(&self) -> InfoContext {
+        // count selected items still matched by the pattern
+        let mut visible = 0;
+        if !self.selected.is_empty() {
+            visible = self
+                .matches
+                .iter()
+                .filter(|&&m| self.selected.contains(m))
+                .count();
+        }
+
+        InfoContext {
+            matched: self.matches.len(),
+            total: self.items.len(),
+            selected: self.selected.len(),
+            hidden: self.selected.len() - visible,
+            mode: match self.vim_mode {
+                true => Some(self.mode),
+                false => None,
+            },
+            case: self.matcher_config.case_mode,
+        }
+    }
+
+    // text drawn at the end of the pattern line
+    fn status(&self) -> String {
+        if let Some(message) = self.message {
+            return message.to_string();
+        }
+
+        let info = self.info_context();
+        if let Some(format) = &self.info_format {
+            return format(&info);
+        }
+
+        // indicators of the active modes
+        let mut parts = Vec::new();
+        if self.selection_count && info.selected > 0 {
+            let mut count = format!("{} selected", info.selected);
+            if info.hidden > 0 {
+                count.push_str(&format!(" ({} hidden)", info.hidden));
+            }
+            parts.push(count);
+        }
+        if let Some(mode) = info.mode {
+            parts.push(match mode {
+                EditMode::Insert => "[INSERT]".to_string(),
+                EditMode::Normal => "[NORMAL]".to_string(),
+            });
+        }
+        // the default case mode isn't worth the space
+        if info.case != CaseMode::default() {
+            parts.push(format!("[{}]", info.case));
+        }
+        parts.join(" ")
+    }
+
+    // shows or hides the position marker for current index
+    fn position(&mut self, show: bool) -> Result<()> {
+        if !self.list_fits() {
+            return Ok(());
+        }
+        let marker = match show {
+            true => self.markers.pointer.clone(),
+            false => " ".repeat(self.markers.pointer.width()),
+        };
+
+        let (left, row) = (self.cell_left(self.index), self.row(self.index));
+        let full_row = self.styled && self.highlight_row;
+        if let Some(drawn) = self.drawn_row(self.index) {
+            drawn.current = show;
+        }
+        let reverse = full_row && show;
+
+        // fill the whole row, reverse video lasts until the marker is drawn
+        if full_row {
+            if reverse {
+                self.writer.queue(SetAttribute(Attribute::Reverse))?;
+            }
+            let blank = " ".repeat(self.cell_width() as usize);
+            self.writer
+                .queue(self.move_to(left, row))?
+                .write_all(blank.as_bytes())?;
+        }
+
+        // repaint the row if the highlighted row looks different
+        if full_row || (self.styled && self.theme.current != self.theme.normal) {
+            self.draw_row(self.index, show, self.key_width())?;
+        }
+
+        self.writer.queue(self.move_to(left, row))?;
+        self.write_styled(marker.as_bytes(), self.theme.current)?;
+        if reverse {
+            self.writer.queue(SetAttribute(Attribute::NoReverse))?;
+        }
+        Ok(())
+    }
+
+    // what was last drawn at the given visible position, if known
+    fn drawn_row(&mut self, i: usize) -> Option<&mut DrawnRow> {
+        self.frame.as_mut()?.rows.get_mut(i)?.as_mut()
+    }
+
+    // writes text in the colors of style if styles are drawn
+    fn write_styled(&mut self, text: &[u8], style: Style) -> Result<()> {
+        match self.styled && style != Style::default() {
+            true => {
+                style.apply(&mut self.writer)?;
+                self.writer.write_all(text)?;
+                self.writer.queue(ResetColor)?;
+            }
+            false => self.writer.write_all(text)?,
+        }
+        Ok(())
+    }
+
+    // shows or hides the selection marker for given row
+    fn selection(&mut self, show: bool, row: u16) -> Result<()> {
+        if !self.list_fits() {
+            return Ok(());
+        }
+        let marker = match show {
+            true => self.markers.selected.clone(),
+            false => " ".repeat(self.markers.selected.width()),
+        };
+
+        if let Some(drawn) = self.drawn_row(row as usize) {
+            drawn.selected = show;
+        }
+
+        // keep the highlighted row reversed
+        let reverse = self.styled && self.highlight_row && row as usize == self.index;
+        if reverse {
+            self.writer.queue(SetAttribute(Attribute::Reverse))?;
+        }
+        self.writer.queue(self.move_to(
+            self.cell_left(row as usize) + self.markers.selected_column(),
+            self.row(row as usize),
+        ))?;
+        self.write_styled(marker.as_bytes(), self.theme.marker)?;
+        if reverse {
+            self.writer.queue(SetAttribute(Attribute::NoReverse))?;
+        }
+        Ok(())
+    }
+
+    // draws the scrollbar right of the list, the whole track is written as the
+    // rows may have been cleared, the thumb is left out if all matches fit
+    fn draw_scrollbar(&mut self) -> Result<()> {
+        let column = self.border_size() + self.list_width();
+        let top = self.border_size();
+        let track = (self.list_bottom() + 1 - top) as usize;
+        let total = self.matches.len();
+        let page = self.page_len();
+
+        // thumb as (rows from the bottom, length), the bottom is the best match
+        let thumb = match total > page {
+            true => {
+                let length = (track * page / total).max(1);
+                let start = (track - length) * self.offset / (total - page);
+                Some((start, length))
+            }
+            false => None,
+        };
+
+        for i in 0..track {
+            let on_thumb = thumb.map_or(false, |(start, length)| i >= start && i < start + length);
+            self.writer
+                .queue(self.move_to(column, self.list_bottom() - i as u16))?;
+            match on_thumb {
+                true => self.write_styled("┃".as_bytes(), self.theme.marker)?,
+                false => self.writer.write_all(b" ")?,
+            }
+        }
+        Ok(())
+    }
+
+    // draws a box around the list
+    fn draw_border(&mut self) -> Result<()> {
+        let columns = self.list_columns();
+        let inner = "─".repeat(columns.saturating_sub(2) as usize);
+        let bottom = self.list_bottom() + 1;
+        // rows are mirrored in the reverse layout
+        let (top_corners, bottom_corners) = match self.layout {
+            Layout::Default => (('┌', '┐'), ('└', '┘')),
+            Layout::Reverse => (('└', '┘'), ('┌', '┐')),
+        };
+
+        self.writer
+            .queue(self.move_to(0, 0))?
+            .write_all(format!("{}{}{}", top_corners.0, inner, top_corners.1).as_bytes())?;
+        for row in 1..bottom {
+            self.writer
+                .queue(self.move_to(0, row))?
+                .write_all("│".as_bytes())?;
+            self.writer
+                .queue(self.move_to(columns.saturating_sub(1), row))?
+                .write_all("│".as_bytes())?;
+        }
+        self.writer
+            .queue(self.move_to(0, bottom))?
+            .write_all(format!("{}{}{}", bottom_corners.0, inner, bottom_corners.1).as_bytes())?;
+
+        Ok(())
+    }
+
+    // moves the position marker to the given match, scrolling the view as
+    // little as possible
+    fn jump_to(&mut self, position: usize) -> Result<()> {
+        let max_index = self.page_len() - 1;
+        if position < self.offset {
+            self.offset = position;
+        } else if position > self.offset + max_index {
+            self.offset = position - max_index;
+        }
+        self.index = position - self.offset;
+
+        self.redraw()
+    }
+
+    // moves the position marker and the view a page up or down on the screen,
+    // keeping the row of the marker unless hitting either end
+    fn page(&mut self, up: bool) -> Result<()> {
+        if self.matches.is_empty() {
+            return Ok(());
+        }
+
+        let page = self.page_len();
+        let last = self.matches.len() - 1;
+        let position = self.offset + self.index;
+        // worse matches are up in the default layout
+        let target = match up == (self.layout == Layout::Default) {
+            true => (position + page).min(last),
+            false => position.saturating_sub(page),
+        };
+
+        self.offset = target
+            .saturating_sub(self.index)
+            .min(self.matches.len().saturating_sub(page));
+        self.jump_to(target)
+    }
+
+    // moves the position marker to the next selected match upwards (forward)
+    // or downwards, wrapping around at the ends
+    fn jump_to_selected(&mut self, forward: bool) -> Result<()> {
+        let positions: Vec<usize> = (0..self.matches.len())
+            .filter(|&p| self.selected.contains(self.matches[p]))
+            .collect();
+
+        let current = self.offset + self.index;
+        let target = match forward {
+            true => positions
+                .iter()
+                .find(|&&p| p > current)
+                .or_else(|| positions.first()),
+            false => positions
+                .iter()
+                .rev()
+                .find(|&&p| p < current)
+                .or_else(|| positions.last()),
+        };
+
+        match target {
+            Some(&position) if position != current => self.jump_to(position),
+            _ => Ok(()),
+        }
+    }
+
+    // moves the highlight a row up or down on the screen
+    fn step(&mut self, up: bool, wrap: bool) -> Result<()> {
+        let up = up == (self.layout == Layout::Default);
+        match (self.grid_columns(), up) {
+            (1, true) => self.move_up(wrap),
+            (1, false) => self.move_down(wrap),
+            (columns, true) => self.step_grid(columns as isize),
+            (columns, false) => self.step_grid(-(columns as isize)),
+        }
+    }
+
+    // moves the highlight by delta positions in a grid, staying on the matches
+    fn step_grid(&mut self, delta: isize) -> Result<()> {
+        let position = self.offset + self.index;
+        let target = match delta >= 0 {
+            true => position.checked_add(delta as usize),
+            false => position.checked_sub(delta.unsigned_abs()),
+        };
+        match target.filter(|&t| t < self.matches.len()) {
+            Some(target) => self.jump_to(target),
+            None => Ok(()),
+        }
+    }
+
+    // moves the position marker up a row, scrolling the view if needed
+    fn move_up(&mut self, wrap: bool) -> Result<()> {
+        // don't go up if there are no more matches
+        if self.offset + self.index + 1 >= self.matches.len() {
+            // unless wrapping around to the first match
+            if wrap && self.matches.len() > 1 {
+                return self.jump_to(0);
+            }
+            return Ok(());
+        }
+
+        // clear previous position marker
+        self.position(false)?;
+
+        match self.index == self.page_len() - 1 {
+            // increment index, the layout depends on it with an inline preview
+            // and the expanded item moves along
+            false => {
+                self.index += 1;
+                if self.inline_preview.is_some() || self.preview.is_some() || self.wrap_highlight {
+                    self.redraw()?;
+                }
+            }
+            // on topmost row -> move the whole view up
+            true => {
+                self.offset += 1;
+                self.redraw()?;
+            }
+        }
+
+        // draw new position marker
+        self.position(true)
+    }
+
+    // moves the position marker down a row, scrolling the view if needed
+    fn move_down(&mut self, wrap: bool) -> Result<()> {
+        // don't go down if already at first match (or there are no matches)
+        if self.offset + self.index == 0 {
+            // unless wrapping around to the last match
+            if wrap && self.matches.len() > 1 {
+                return self.jump_to(self.matches.len() - 1);
+            }
+            return Ok(());
+        }
+
+        // clear previous position marker
+        self.position(false)?;
+
+        match self.index == 0 {
+            // decrement index, the layout depends on it with an inline preview
+            // and the expanded item moves along
+            false => {
+                self.index -= 1;
+                if self.inline_preview.is_some() || self.preview.is_some() || self.wrap_highlight {
+                    self.redraw()?;
+                }
+            }
+            // on bottom row -> move the whole view down
+            true => {
+                self.offset -= 1;
+                self.redraw()?;
+            }
+        }
+
+        // draw new position marker
+        self.position(true)
+    }
+
+    // toggles the selection of the item under the position marker
+    fn toggle_selection(&mut self) -> Result<()> {
+        if self.matches.is_empty() {
+            return Ok(());
+        }
+
+        let current_item = self.matches[self.offset + self.index];
+
+        match self.selected.contains(current_item) {
+            // remove the (existing) selection
+            true => {
+                self.selected.remove(current_item);
+                self.selection(false, self.index as u16)?;
+            }
+            // replace the previous selection, its marker may be anywhere
+            false if self.max_selections == Some(1) => {
+                self.selected.clear();
+                self.selected.insert(current_item);
+                return self.redraw();
+            }
+            // ignore selections over the limit
+            false
+                if self
+                    .max_selections
+                    .map_or(false, |max| self.selected.len() >= max) =>
+            {
+                self.message = Some("selection limit reached");
+            }
+            // add a new selection
+            false => {
+                self.selected.insert(current_item);
+                self.selection(true, self.index as u16)?;
+            }
+        }
+
+        // update the selection count or message
+        let counted = self.selection_count || self.match_count || self.info_format.is_some();
+        match counted || self.message.is_some() {
+            true => self.draw_prompt(),
+            false => Ok(()),
+        }
+    }
+
+    // adds all matches to the selections, in the order of matches
+    fn select_all(&mut self) {
+        let mut room = match self.max_selections {
+            Some(max) => max.saturating_sub(self.selected.len()),
+            None => usize::MAX,
+        };
+        for &item in &self.matches {
+            if room == 0 {
+                break;
+            }
+            if self.selected.insert(item) {
+                room -= 1;
+            }
+        }
+    }
+
+    // keeps offset and index pointing to a visible, existing match
+    fn clamp_position(&mut self) {
+        let max_index = self.page_len() - 1;
+        if self.index > max_index {
+            self.offset += self.index - max_index;
+            self.index = max_index;
+        }
+
+        let last = self.matches.len().saturating_sub(1);
+        if self.offset + self.index > last {
+            self.index = self.index.min(last);
+            self.offset = last - self.index;
+        }
+
+        // don't leave rows empty if there are more matches above the view
+        let max_offset = self.matches.len().saturating_sub(max_index + 1);
+        if self.offset > max_offset {
+            self.index += self.offset - max_offset;
+            self.offset = max_offset;
+        }
+
+        // keep whole rows of a grid in view
+        let columns = self.grid_columns();
+        let misaligned = self.offset % columns;
+        if misaligned > 0 {
+            self.offset -= misaligned;
+            self.index += misaligned;
+            if self.index > max_index {
+                self.offset += columns;
+                self.index -= columns;
+            }
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let items = self.items;
+        // compares equally scored items, by position in list if equal
+        let (order, custom) = (self.tiebreak, &self.tiebreak_with);
+        let tiebreak = |a: usize, b: usize| {
+            match custom {
+                Some(compare) => compare(items[a], items[b]),
+                None => order.compare(items[a], items[b]),
+            }
+            .then(a.cmp(&b))
+        };
+
+        // items not matching a pattern don't match it with characters appended
+        // either, so only the previous matches need scoring, unless limited
+        // (or scores may grow past the minimum, or a custom matcher disagrees,
+        // or appending makes an alternative, a negated term or a suffix match
+        // more)
+        let narrowing = self.limit.is_none()
+            && self.min_score.is_none()
+            && self.custom_matcher.is_none()
+            && !self.pattern.contains(['!', '|', '$'])
+            && matches!(&self.matched_pattern, Some(old) if !old.is_empty() && self.pattern.starts_with(old.as_str()));
+        let previous = std::mem::take(&mut self.matches);
+        let highlighted = previous.get(self.offset + self.index).copied();
+        self.scores.clear();
+        self.matched_pattern = None;
+
+        // match nothing until the pattern is long enough
+        if self.below_min_query_len() {
+            self.offset = 0;
+            self.index = 0;
+            return;
+        }
+        self.matched_pattern = Some(self.pattern.clone());
+
+        match self.pattern.is_empty() {
+            // match all items if pattern is empty
+            true => {
+                // add all items and sort them, only keeping the first items
+                // of a limited list (without sorting all of them)
+                match (self.limit, self.assume_sorted) {
+                    (Some(limit), true) => self.matches.extend(0..items.len().min(limit)),
+                    (Some(limit), false) => {
+                        for i in 0..items.len() {
+                            push_best(&mut self.matches, i, limit, |&a, &b| tiebreak(a, b));
+                        }
+                    }
+                    (None, _) => self.matches.extend(0..items.len()),
+                }
+                if !self.assume_sorted {
+                    self.matches.sort_unstable_by(|&a, &b| tiebreak(a, b));
+                }
+                if let Some(limit) = self.limit {
+                    self.matches.truncate(limit);
+                }
+                self.scores.resize(self.matches.len(), 0);
+                // there can't be less matches than previously
+                //   -> offset + index will point to an existing item
+                //   (unless limited, position is clamped when drawing)
+            }
+            // fuzzy match items with non-empty pattern
+            false => {
+                let (config, matcher) = (&self.matcher_config, &self.matcher);
+                let (pattern, boost) = (&self.pattern, self.exact_match_boost);
+                // items with corresponding scores (for sorting), exact matches
+                // are moved to the top if boosted
+                let mut scored = Vec::new();
+                let compare =
+                    |a: &(usize, bool, i64), b: &(usize, bool, i64)| by_rank(a, b, tiebreak);
+
+                let key_separator = self.two_column.filter(|_| self.match_key);
+                let fields = &self.fields;
+                let min_score = self.min_score;
+                let custom = &self.custom_matcher;
+                let ansi = self.ansi;
+
+                let candidates: Box<dyn Iterator<Item = usize>> = match narrowing {
+                    true => Box::new(previous.into_iter()),
+                    false => Box::new(0..items.len()),
+                };
+
+                for i in candidates {
+                    let source = match ansi {
+                        true => text::strip_ansi(items[i]),
+                        false => Cow::Borrowed(items[i]),
+                    };
+                    let item = match (fields, key_separator) {
+                        (Some((delimiter, fields)), _) => {
+                            Cow::Owned(select_fields(&source, *delimiter, fields).0)
+                        }
+                        (None, Some(separator)) => {
+                            Cow::Borrowed(split_columns(&source, separator).0)
+                        }
+                        (None, None) => Cow::Borrowed(source.as_ref()),
+                    };
+                    let score = match custom {
+                        Some(custom) => custom.score(&item, pattern),
+                        None => config.score(matcher, &item, pattern, false).map(|(s, _)| s),
+                    };
+                    let score = score.filter(|&score| min_score.map_or(true, |min| score >= min));
+                    if let Some(score) = score {
+                        let exact = boost && config.is_exact(&item, pattern);
+                        // drop all but the best matches when the buffer is full
+                        match self.limit {
+                            Some(limit) => {
+                                push_best(&mut scored, (i, exact, score), limit, compare)
+                            }
+                            None => scored.push((i, exact, score)),
+                        }
+                    }
+                }
+
+                scored.sort_unstable_by(compare);
+                if let Some(limit) = self.limit {
+                    scored.truncate(limit);
+                }
+
+                // add sorted matches
+                self.matches.extend(scored.iter().map(|&(i, _e, _s)| i));
+                self.scores.extend(scored.iter().map(|&(_i, _e, s)| s));
+
+                // reset offset so that matches with best scores are visible
+                self.offset = 0;
+
+                match self.matches.is_empty() {
+                    // reset index back to 0 if there are no matches
+                    true => self.index = 0,
+                    false => {
+                        if self.index >= self.matches.len() {
+                            // set index to point to the last item
+                            self.index = self.matches.len() - 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        // follow the highlighted item to its new position
+        if let Some(item) = highlighted.filter(|_| self.keep_highlight) {
+            if let Some(position) = self.matches.iter().position(|&m| m == item) {
+                self.index = self.index.min(position);
+                self.offset = position - self.index;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::{Cell, RefCell},
+        collections::VecDeque,
+        ffi::OsStr,
+        rc::Rc,
+    };
+
+    const FRUITS: &[&str] = &["apple", "banana", "cherry"];
+
+    // a picker on an 80x24 terminal drawing into a buffer
+    fn picker<'a>() -> Fz<'a, Vec<u8>> {
+        Fz::with_size(Vec::new(), 80, 24)
+    }
+
+    // fills the matches like start does, without touching the terminal
+    fn load<'a>(fz: &mut Fz<'a, Vec<u8>>, list: &'a [&'a str]) {
+        fz.items = list;
+        fz.update_matches();
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> crossterm::Result<Event> {
+        Ok(Event::Key(KeyEvent::new(code, modifiers)))
+    }
+
+    // handles a key press, returning how picking ended if it did
+    fn press(fz: &mut Fz<'_, Vec<u8>>, code: KeyCode, modifiers: KeyModifiers) -> Option<Exit> {
+        fz.handle_event(key(code, modifiers), Instant::now())
+            .unwrap()
+    }
+
+    fn type_text(fz: &mut Fz<'_, Vec<u8>>, text: &str) {
+        for c in text.chars() {
+            assert_eq!(press(fz, KeyCode::Char(c), KeyModifiers::NONE), None);
+        }
+    }
+
+    fn highlighted<'a>(fz: &Fz<'a, Vec<u8>>) -> &'a str {
+        fz.items[fz.matches[fz.offset + fz.index]]
+    }
+
+    // a console replaying events, when they run out polling moves the clock
+    // past the timeout instead of waiting and reading fails
+    struct Script {
+        // events with how long reading waits for them
+        events: VecDeque<(Duration, Event)>,
+        clock: Rc<MockClock>,
+        polls: Rc<Cell<usize>>,
+        raw: Rc<Cell<bool>>,
+        cursor: Option<(u16, u16)>, // None if asking for it fails
+        poll_fails: bool,
+    }
+
+    impl Console for Script {
+        fn enable_raw_mode(&mut self) -> crossterm::Result<()> {
+            self.raw.set(true);
+            Ok(())
+        }
+
+        fn disable_raw_mode(&mut self) -> crossterm::Result<()> {
+            self.raw.set(false);
+            Ok(())
+        }
+
+        fn position(&mut self) -> crossterm::Result<(u16, u16)> {
+            self.cursor.ok_or_else(|| {
+                CrosstermError::IoError(IoError::new(io::ErrorKind::TimedOut, "no cursor position"))
+            })
+        }
+
+        fn size(&mut self) -> crossterm::Result<(u16, u16)> {
+            Ok((80, 24))
+        }
+
+        fn poll(&mut self, timeout: Duration) -> crossterm::Result<bool> {
+            self.polls.set(self.polls.get() + 1);
+            if self.poll_fails {
+                let error = IoError::new(io::ErrorKind::BrokenPipe, "poll failed");
+                return Err(CrosstermError::IoError(error));
+            }
+            match self.events.front_mut() {
+                Some((wait, _)) if *wait <= timeout => {
+                    self.clock.advance(*wait);
+                    *wait = Duration::ZERO;
+                    Ok(true)
+                }
+                Some((wait, _)) => {
+                    self.clock.advance(timeout);
+                    *wait -= timeout;
+                    Ok(false)
+                }
+                None => {
+                    self.clock.advance(timeout);
+                    Ok(false)
+                }
+            }
+        }
+
+        fn read(&mut self) -> crossterm::Result<Event> {
+            let (wait, event) = self.events.pop_front().ok_or_else(|| {
+                CrosstermError::IoError(IoError::new(io::ErrorKind::UnexpectedEof, "script ended"))
+            })?;
+            self.clock.advance(wait);
+            Ok(event)
+        }
+    }
+
+    // what a script saw while the picker ran
+    struct Replay {
+        polls: Rc<Cell<usize>>,
+        raw: Rc<Cell<bool>>,
+    }
+
+    // makes the picker read events from a script timed by clock, on an 80x24
+    // terminal with the cursor at the top left
+    fn script(fz: &mut Fz<'_, Vec<u8>>, clock: &Rc<MockClock>, events: Vec<Event>) -> Replay {
+        script_at(fz, clock, events, (0, 0))
+    }
+
+    fn script_at(
+        fz: &mut Fz<'_, Vec<u8>>,
+        clock: &Rc<MockClock>,
+        events: Vec<Event>,
+        cursor: (u16, u16),
+    ) -> Replay {
+        let events = events.into_iter().map(|event| (Duration::ZERO, event));
+        install(fz, clock, events.collect(), Some(cursor))
+    }
+
+    // like script, but each event arrives after a pause
+    fn script_paced(
+        fz: &mut Fz<'_, Vec<u8>>,
+        clock: &Rc<MockClock>,
+        events: Vec<(Duration, Event)>,
+    ) -> Replay {
+        install(fz, clock, events, Some((0, 0)))
+    }
+
+    fn install(
+        fz: &mut Fz<'_, Vec<u8>>,
+        clock: &Rc<MockClock>,
+        events: Vec<(Duration, Event)>,
+        cursor: Option<(u16, u16)>,
+    ) -> Replay {
+        let (polls, raw) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(false)));
+        fz.console = Box::new(Script {
+            events: events.into(),
+            clock: Rc::clone(clock),
+            polls: Rc::clone(&polls),
+            raw: Rc::clone(&raw),
+            cursor,
+            poll_fails: false,
+        });
+        Replay { polls, raw }
+    }
+
+    fn keys(codes: &[KeyCode]) -> Vec<Event> {
+        codes
+            .iter()
+            .map(|&code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+            .collect()
+    }
+
+    // key presses typing text
+    fn typing(text: &str) -> Vec<Event> {
+        keys(&text.chars().map(KeyCode::Char).collect::<Vec<_>>())
+    }
+
+    // everything drawn so far
+    fn output(fz: &Fz<'_, Vec<u8>>) -> String {
+        String::from_utf8_lossy(&fz.writer.inner).into_owned()
+    }
+
+    #[test]
+    fn ctrl_m_accepts_without_typing() {
+        let mut fz = picker();
+        load(&mut fz, FRUITS);
+
+        let exit = press(&mut fz, KeyCode::Char('m'), KeyModifiers::CONTROL);
+        assert_eq!(exit, Some(Exit::Accept));
+        assert_eq!(fz.pattern, "");
+
+        // without CONTROL it's typed
+        type_text(&mut fz, "m");
+        assert_eq!(fz.pattern, "m");
+    }
+
+    #[test]
+    fn ctrl_n_and_ctrl_p_navigate_without_typing() {
+        let mut fz = picker();
+        load(&mut fz, FRUITS);
+
+        assert_eq!(
+            press(&mut fz, KeyCode::Char('p'), KeyModifiers::CONTROL),
+            None
+        );
+        assert_eq!(highlighted(&fz), "banana");
+        assert_eq!(
+            press(&mut fz, KeyCode::Char('n'), KeyModifiers::CONTROL),
+            None
+        );
+        assert_eq!(highlighted(&fz), "apple");
+        assert_eq!(fz.pattern, "");
+        assert_eq!(fz.matches.len(), 3);
+    }
+
+    #[test]
+    fn short_patterns_match_nothing() {
+        let mut fz = picker().min_query_len(2);
+        load(&mut fz, FRUITS);
+        assert!(fz.matches.is_empty());
+
+        type_text(&mut fz, "a");
+        assert!(fz.matches.is_empty());
+        assert!(output(&fz).contains("type 2 characters to search"));
+
+        type_text(&mut fz, "n");
+        assert_eq!(fz.matches.len(), 1);
+    }
+
+    #[test]
+    fn snapshot_restores_after_changes() {
+        let mut fz = picker();
+        load(&mut fz, FRUITS);
+        type_text(&mut fz, "a");
+        press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut fz, KeyCode::Left, KeyModifiers::NONE);
+        let state = fz.snapshot();
+        assert_eq!(state.pattern, "a");
+        assert_eq!(state.cursor, 0);
+        assert_eq!(state.selected.len(), 1);
+
+        type_text(&mut fz, "pp");
+        press(&mut fz, KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_ne!(fz.snapshot(), state);
+
+        fz.restore(state.clone());
+        assert_eq!(fz.snapshot(), state);
+        // typing continues at the restored cursor
+        type_text(&mut fz, "b");
+        assert_eq!(fz.pattern, "ba");
+    }
+
+    const CASES: &[&str] = &["Apple", "apple", "APPLE"];
+
+    // items matched by pattern under mode, in list order
+    fn matched_with_case(mode: CaseMode, pattern: &str) -> Vec<&'static str> {
+        let mut fz = picker().case_mode(mode);
+        load(&mut fz, CASES);
+        type_text(&mut fz, pattern);
+        let mut matched: Vec<usize> = fz.matches.clone();
+        matched.sort_unstable();
+        matched.into_iter().map(|m| CASES[m]).collect()
+    }
+
+    #[test]
+    fn case_modes_filter_mixed_case() {
+        assert_eq!(matched_with_case(CaseMode::Smart, "app"), CASES);
+        assert_eq!(matched_with_case(CaseMode::Smart, "App"), ["Apple"]);
+        assert_eq!(matched_with_case(CaseMode::Insensitive, "App"), CASES);
+        assert_eq!(matched_with_case(CaseMode::Sensitive, "app"), ["apple"]);
+    }
+
+    #[test]
+    fn alt_c_cycles_case_mode() {
+        let mut fz = picker();
+        load(&mut fz, CASES);
+        type_text(&mut fz, "App");
+        assert_eq!(fz.matches.len(), 1);
+
+        press(&mut fz, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(fz.matcher_config.case_mode, CaseMode::Insensitive);
+        assert_eq!(fz.matches.len(), 3);
+        assert!(output(&fz).contains("[ignore-case]"));
+
+        // back to the default after cycling through all modes
+        press(&mut fz, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(fz.status(), "[respect-case]");
+        press(&mut fz, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(fz.status(), "");
+    }
+
+    #[test]
+    fn border_shrinks_the_list() {
+        let plain = picker();
+        let bordered = picker().border(true);
+        assert_eq!(bordered.max_rows() + 2, plain.max_rows());
+        assert_eq!(bordered.list_width() + 2, plain.list_width());
+        assert_eq!(bordered.cell_left(0), 1);
+        assert_eq!(bordered.list_bottom() + 1, plain.list_bottom());
+    }
+
+    #[test]
+    fn final_matches_follow_the_last_query() {
+        let mut fz = picker().keep_final_matches(true);
+        load(&mut fz, FRUITS);
+        type_text(&mut fz, "e");
+        type_text(&mut fz, "r");
+
+        let outcome = fz.finish(FRUITS, Exit::Accept).unwrap();
+        assert_eq!(outcome.final_matches, Some(vec!["cherry"]));
+        assert_eq!(outcome.selected, ["cherry"]);
+    }
+
+    #[test]
+    fn vim_mode_navigates_only_in_normal_mode() {
+        let mut fz = picker().vim_mode(true);
+        load(&mut fz, FRUITS);
+        assert_eq!(fz.mode, EditMode::Insert);
+
+        // ESC switches to normal mode instead of cancelling
+        assert_eq!(press(&mut fz, KeyCode::Esc, KeyModifiers::NONE), None);
+        assert_eq!(fz.mode, EditMode::Normal);
+        type_text(&mut fz, "k");
+        assert_eq!(highlighted(&fz), "banana");
+        type_text(&mut fz, "j");
+        assert_eq!(highlighted(&fz), "apple");
+        assert_eq!(fz.pattern, "");
+
+        // back in insert mode j and k are typed
+        type_text(&mut fz, "i");
+        assert_eq!(fz.mode, EditMode::Insert);
+        type_text(&mut fz, "jk");
+        assert_eq!(fz.pattern, "jk");
+
+        // ESC in normal mode cancels
+        press(&mut fz, KeyCode::Esc, KeyModifiers::NONE);
+        let exit = press(&mut fz, KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(exit, Some(Exit::Cancel));
+    }
+
+    #[test]
+    fn idle_timeout_with_mock_clock() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker()
+            .clock(Rc::clone(&clock))
+            .idle_timeout(Duration::from_secs(60));
+        let replay = script(&mut fz, &clock, keys(&[KeyCode::Down]));
+        let start = clock.now();
+
+        let outcome = fz.run(FRUITS).unwrap();
+        assert_eq!(outcome.status, Status::TimedOut);
+        assert!(outcome.selected.is_empty());
+        // the key press reset the timer, then the picker waited a full minute
+        assert_eq!(clock.now() - start, Duration::from_secs(60));
+        assert_eq!(replay.polls.get(), 2);
+        assert!(!replay.raw.get());
+    }
+
+    #[test]
+    fn poll_errors_end_picking() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker()
+            .clock(Rc::clone(&clock))
+            .idle_timeout(Duration::from_secs(60));
+        let raw = Rc::new(Cell::new(false));
+        fz.console = Box::new(Script {
+            events: VecDeque::new(),
+            clock: Rc::clone(&clock),
+            polls: Rc::new(Cell::new(0)),
+            raw: Rc::clone(&raw),
+            cursor: Some((0, 0)),
+            poll_fails: true,
+        });
+
+        let start = clock.now();
+
+        // the error ends picking instead of counting as no input until the
+        // timeout
+        assert!(fz.run(FRUITS).is_err());
+        assert_eq!(clock.now(), start);
+        assert!(!raw.get());
+    }
+
+    const PADDED: &[&str] = &["  one ", " two"];
+
+    #[test]
+    fn map_result_trims_single_and_multi() {
+        let clock = Rc::new(MockClock::new());
+        let trimmed = |keys_pressed: &[KeyCode]| {
+            let mut fz = picker().map_result(|item| item.trim().to_owned());
+            script(&mut fz, &clock, keys(keys_pressed));
+            fz.select_mapped(PADDED).unwrap()
+        };
+
+        assert_eq!(trimmed(&[KeyCode::Enter]), ["one"]);
+        assert_eq!(
+            trimmed(&[KeyCode::Tab, KeyCode::Up, KeyCode::Tab, KeyCode::Enter]),
+            ["one", "two"]
+        );
+    }
+
+    #[test]
+    fn accepting_without_matches_keeps_the_query() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker();
+        let mut events = typing("xyz");
+        events.extend(keys(&[KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+
+        let outcome = fz.run(FRUITS).unwrap();
+        assert_eq!(outcome.status, Status::NoMatches);
+        assert!(outcome.selected.is_empty());
+        assert_eq!(outcome.query, "xyz");
+    }
+
+    #[test]
+    fn selections_take_precedence_over_no_matches() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker();
+        let mut events = keys(&[KeyCode::Tab]);
+        events.extend(typing("xyz"));
+        events.extend(keys(&[KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+
+        let outcome = fz.run(FRUITS).unwrap();
+        assert_eq!(outcome.status, Status::Accepted);
+        assert_eq!(outcome.selected, ["apple"]);
+    }
+
+    fn scroll(fz: &mut Fz<'_, Vec<u8>>, kind: MouseEventKind) {
+        let event = Event::Mouse(MouseEvent {
+            kind,
+            column: 0,
+            row: 0,
+            modifiers: KeyModifiers::NONE,
+        });
+        fz.handle_event(Ok(event), Instant::now()).unwrap();
+    }
+
+    #[test]
+    fn keyboard_and_scroll_wrap_separately() {
+        for &(keyboard, wheel) in &[(false, false), (false, true), (true, false), (true, true)] {
+            let mut fz = picker().keyboard_wrap(keyboard).scroll_wrap(wheel);
+            load(&mut fz, FRUITS);
+            let (first, last) = ("apple", "cherry");
+            let wrapped = |wrap: bool, to: &'static str, from: &'static str| match wrap {
+                true => to,
+                false => from,
+            };
+
+            // past the first match
+            press(&mut fz, KeyCode::Down, KeyModifiers::NONE);
+            assert_eq!(highlighted(&fz), wrapped(keyboard, last, first));
+            fz.jump_to(0).unwrap();
+            scroll(&mut fz, MouseEventKind::ScrollDown);
+            assert_eq!(highlighted(&fz), wrapped(wheel, last, first));
+
+            // past the last match
+            fz.jump_to(2).unwrap();
+            press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+            assert_eq!(highlighted(&fz), wrapped(keyboard, first, last));
+            fz.jump_to(2).unwrap();
+            scroll(&mut fz, MouseEventKind::ScrollUp);
+            assert_eq!(highlighted(&fz), wrapped(wheel, first, last));
+        }
+    }
+
+    #[test]
+    fn scores_grow_with_more_specific_patterns() {
+        let config = MatcherConfig::default();
+        let scores: Vec<i64> = ["s", "sr", "src", "src/", "src/m", "src/main"]
+            .iter()
+            .map(|pattern| score_item(&config, "src/main.rs", pattern).unwrap().0)
+            .collect();
+        assert!(
+            scores.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            scores
+        );
+
+        let (_, indices) = score_item(&config, "src/main.rs", "main").unwrap();
+        assert_eq!(indices, [4, 5, 6, 7]);
+        assert_eq!(score_item(&config, "src/main.rs", "xyz"), None);
+    }
+
+    // items "item 00" to "item {n-1}", sorted as listed
+    fn numbered(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("item {:02}", i)).collect()
+    }
+
+    #[test]
+    fn redraw_fixes_a_stale_offset() {
+        let items = numbered(10);
+        let list: Vec<&str> = items.iter().map(String::as_str).collect();
+        // 5 visible rows
+        let mut fz = Fz::with_size(Vec::new(), 80, 6);
+        load(&mut fz, &list);
+        fz.offset = 8;
+        fz.index = 0;
+
+        fz.redraw().unwrap();
+        assert_eq!((fz.offset, fz.index), (5, 3));
+        assert_eq!(highlighted(&fz), "item 08");
+        assert_eq!(fz.viewport().0.len(), 5);
+    }
+
+    #[test]
+    fn status_counts_hidden_selections() {
+        let mut fz = picker().selection_count(true);
+        load(&mut fz, FRUITS);
+        // select apple and banana
+        press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(fz.status(), "2 selected");
+
+        // apple doesn't match
+        type_text(&mut fz, "nan");
+        assert_eq!(fz.info_context().hidden, 1);
+        assert_eq!(fz.status(), "2 selected (1 hidden)");
+        assert!(output(&fz).contains("2 selected (1 hidden)"));
+    }
+
+    #[test]
+    fn accept_highlight_ignores_selections() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker();
+        let mut events = keys(&[KeyCode::Tab, KeyCode::Up, KeyCode::Tab, KeyCode::Up]);
+        events.push(Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT)));
+        script(&mut fz, &clock, events);
+
+        let outcome = fz.run(FRUITS).unwrap();
+        assert_eq!(outcome.selected, ["cherry"]);
+        assert_eq!(fz.selected.len(), 2);
+    }
+
+    // matches of a fresh picker for pattern under mode, scoring every item
+    fn fresh_matches(list: &'static [&'static str], mode: CaseMode, pattern: &str) -> Vec<usize> {
+        let mut fz = picker().case_mode(mode);
+        fz.pattern = pattern.to_owned();
+        load(&mut fz, list);
+        fz.matches
+    }
+
+    #[test]
+    fn toggling_case_mode_recomputes_matches() {
+        let mut fz = picker();
+        load(&mut fz, CASES);
+        type_text(&mut fz, "A");
+        assert_eq!(fz.matches, fresh_matches(CASES, CaseMode::Smart, "A"));
+
+        // each mode, then narrowing under it
+        for mode in [CaseMode::Insensitive, CaseMode::Sensitive, CaseMode::Smart] {
+            press(&mut fz, KeyCode::Char('c'), KeyModifiers::ALT);
+            assert_eq!(fz.matcher_config.case_mode, mode);
+            assert_eq!(fz.matches, fresh_matches(CASES, mode, &fz.pattern));
+            type_text(&mut fz, "p");
+            assert_eq!(fz.matches, fresh_matches(CASES, mode, &fz.pattern));
+        }
+    }
+
+    #[test]
+    fn inline_preview_follows_the_highlight() {
+        let mut fz = picker().inline_preview(|item| format!("about {}\nmore", item));
+        load(&mut fz, FRUITS);
+        fz.redraw().unwrap();
+        assert!(output(&fz).contains("about apple"));
+        assert!(!output(&fz).contains("about banana"));
+
+        fz.writer.inner.clear();
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        assert!(output(&fz).contains("about banana"));
+        assert!(!output(&fz).contains("about apple"));
+        // the rows above the highlight make room for the preview lines
+        assert_eq!(fz.row(0), fz.row(1) + 1 + fz.preview_rows());
+    }
+
+    #[test]
+    fn assume_sorted_keeps_the_list_order() {
+        const UNSORTED: &[&str] = &["cherry", "apple", "banana"];
+        let mut fz = picker();
+        load(&mut fz, UNSORTED);
+        assert_eq!(fz.matches, [1, 2, 0]);
+
+        let mut fz = picker().assume_sorted(true);
+        load(&mut fz, UNSORTED);
+        assert_eq!(fz.matches, [0, 1, 2]);
+        // a pattern still ranks the matches
+        type_text(&mut fz, "an");
+        assert_eq!(fz.matches, [2]);
+    }
+
+    #[test]
+    fn gg_chord_expires_after_a_pause() {
+        let mut fz = picker().vim_mode(true);
+        load(&mut fz, FRUITS);
+        press(&mut fz, KeyCode::Esc, KeyModifiers::NONE);
+        type_text(&mut fz, "kk");
+        assert_eq!(highlighted(&fz), "cherry");
+
+        let g = || key(KeyCode::Char('g'), KeyModifiers::NONE);
+        let start = Instant::now();
+        // too slow, the second g starts a new chord
+        fz.handle_event(g(), start).unwrap();
+        fz.handle_event(g(), start + CHORD_TIMEOUT).unwrap();
+        assert_eq!(highlighted(&fz), "cherry");
+        // quick enough
+        fz.handle_event(g(), start + CHORD_TIMEOUT + Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(highlighted(&fz), "apple");
+
+        // another key in between cancels the chord
+        type_text(&mut fz, "k");
+        fz.handle_event(g(), start).unwrap();
+        type_text(&mut fz, "k");
+        fz.handle_event(g(), start).unwrap();
+        assert_eq!(highlighted(&fz), "cherry");
+    }
+
+    // the drawn row of a long item in a narrow list cut in style
+    fn truncated(style: TruncationStyle) -> String {
+        const LONG: &[&str] = &["abcdefghijklmnopqrstuvwxyz"];
+        let mut fz = Fz::with_size(Vec::new(), 12, 5).truncation_style(style);
+        fz.styled = true;
+        load(&mut fz, LONG);
+        fz.redraw().unwrap();
+        output(&fz)
+    }
+
+    #[test]
+    fn truncation_styles_mark_the_cut() {
+        let dim = "\x1b[2m";
+        let ellipsis = truncated(TruncationStyle::Ellipsis);
+        assert!(ellipsis.contains(".."));
+        assert!(!ellipsis.contains(dim));
+        assert!(!ellipsis.contains('z'));
+
+        let fade = truncated(TruncationStyle::Fade);
+        assert!(!fade.contains(".."));
+        assert!(fade.contains(dim));
+
+        let clip = truncated(TruncationStyle::Clip);
+        assert!(!clip.contains(".."));
+        assert!(!clip.contains(dim));
+        assert!(!clip.contains('z'));
+    }
+
+    #[test]
+    fn viewport_follows_the_position() {
+        let items = numbered(10);
+        let list: Vec<&str> = items.iter().map(String::as_str).collect();
+        // 5 visible rows
+        let mut fz = Fz::with_size(Vec::new(), 80, 6);
+        load(&mut fz, &list);
+        let slice = |from: usize| list[from..from + 5].to_vec();
+        assert_eq!(fz.viewport(), (slice(0), 0));
+
+        // stepping within the page keeps the slice
+        for _ in 0..4 {
+            press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        }
+        assert_eq!(fz.viewport(), (slice(0), 4));
+
+        // stepping past it scrolls by one
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(fz.viewport(), (slice(1), 4));
+
+        // the last page
+        press(&mut fz, KeyCode::PageUp, KeyModifiers::NONE);
+        assert_eq!(fz.viewport().0, slice(5));
+        assert_eq!(highlighted(&fz), fz.viewport().0[fz.viewport().1]);
+    }
+
+    #[test]
+    fn alt_n_and_alt_p_visit_each_selection() {
+        let items = numbered(30);
+        let list: Vec<&str> = items.iter().map(String::as_str).collect();
+        let mut fz = Fz::with_size(Vec::new(), 80, 6);
+        load(&mut fz, &list);
+        fz.selected = vec![25, 3, 12].into();
+
+        for expected in ["item 03", "item 12", "item 25", "item 03"] {
+            press(&mut fz, KeyCode::Char('n'), KeyModifiers::ALT);
+            assert_eq!(highlighted(&fz), expected);
+            assert!(fz.viewport().0.contains(&expected));
+        }
+        for expected in ["item 25", "item 12", "item 03"] {
+            press(&mut fz, KeyCode::Char('p'), KeyModifiers::ALT);
+            assert_eq!(highlighted(&fz), expected);
+        }
+    }
+
+    #[test]
+    fn reads_without_polling_when_nothing_is_timed() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().clock(Rc::clone(&clock));
+        let replay = script(&mut fz, &clock, keys(&[KeyCode::Up, KeyCode::Enter]));
+
+        assert_eq!(fz.select(FRUITS).unwrap().as_ref(), ["banana"]);
+        assert_eq!(replay.polls.get(), 0);
+    }
+
+    #[test]
+    fn exact_match_boost_moves_the_item_to_the_top() {
+        // both score the same, a/b sorts first
+        const PATHS: &[&str] = &["ab", "a/b"];
+        let mut fz = picker();
+        load(&mut fz, PATHS);
+        type_text(&mut fz, "ab");
+        assert_eq!(highlighted(&fz), "a/b");
+
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().exact_match_boost(true);
+        let mut events = typing("ab");
+        events.extend(keys(&[KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+        assert_eq!(fz.select(PATHS).unwrap().as_ref(), ["ab"]);
+    }
+
+    #[test]
+    fn info_format_renders_the_context() {
+        let mut fz = picker().case_mode(CaseMode::Sensitive).info_format(|info| {
+            format!(
+                "<{}/{} sel {} {:?}>",
+                info.matched, info.total, info.selected, info.case
+            )
+        });
+        load(&mut fz, FRUITS);
+        press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+        type_text(&mut fz, "an");
+        assert!(output(&fz).contains("<1/3 sel 1 Sensitive>"));
+    }
+
+    #[test]
+    fn multi_line_records_match_their_second_line() {
+        let input = "first\nalpha\n\n\nsecond\nbeta\n\nthird\ngamma\n";
+        let records = split_records(input, RecordDelimiter::BlankLine);
+        assert_eq!(records, ["first\nalpha", "second\nbeta", "third\ngamma"]);
+
+        let mut fz = picker();
+        load(&mut fz, &records);
+        type_text(&mut fz, "beta");
+        assert_eq!(fz.matches.len(), 1);
+        assert_eq!(highlighted(&fz), "second\nbeta");
+        // drawn as the first line
+        assert!(output(&fz).contains("second"));
+    }
+
+    #[test]
+    fn print_query_emits_the_query_on_accept() {
+        let clock = Rc::new(MockClock::new());
+        let mut printed = Vec::new();
+        let mut fz = picker().print_query(&mut printed);
+        let mut events = typing("an");
+        events.extend(keys(&[KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+        assert_eq!(fz.select(FRUITS).unwrap().as_ref(), ["banana"]);
+        drop(fz);
+        assert_eq!(printed, b"an\n");
+
+        // nothing on cancel
+        let mut printed = Vec::new();
+        let mut fz = picker().print_query(&mut printed);
+        let mut events = typing("an");
+        events.extend(keys(&[KeyCode::Esc]));
+        script(&mut fz, &clock, events);
+        assert!(fz.select(FRUITS).unwrap().is_empty());
+        drop(fz);
+        assert!(printed.is_empty());
+    }
+
+    #[test]
+    fn inline_teardown_restores_the_start() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().inline(5);
+        // after printing "Pick: " on the second to last row
+        script_at(&mut fz, &clock, keys(&[KeyCode::Esc]), (6, 22));
+        fz.select(FRUITS).unwrap();
+
+        // the picker starts on the next row, scrolling the terminal by 4 rows
+        assert_eq!((fz.top, fz.start), (19, (6, 18)));
+        let output = output(&fz);
+        assert!(output.starts_with("\x1b[4S"));
+        // the start row isn't drawn over
+        assert!(!output.contains("\x1b[19;1H"));
+        assert!(output.ends_with("\x1b[20;1H\x1b[J\x1b[19;7H\x1b[?7h"));
+    }
+
+    #[test]
+    fn tiebreak_with_reverses_alphabetical_order() {
+        let mut fz = picker().tiebreak_with(|a, b| b.cmp(a));
+        load(&mut fz, FRUITS);
+        assert_eq!(fz.matches, [2, 1, 0]);
+
+        // all score the same for ab
+        const TIED: &[&str] = &["ab", "a/b", "ab_ab"];
+        let mut fz = picker().tiebreak_with(|a, b| b.cmp(a));
+        load(&mut fz, TIED);
+        type_text(&mut fz, "ab");
+        assert_eq!(fz.matches, [2, 0, 1]);
+
+        // items compared equal stay in list order
+        let mut fz = picker().tiebreak_with(|_, _| Ordering::Equal);
+        load(&mut fz, TIED);
+        type_text(&mut fz, "ab");
+        assert_eq!(fz.matches, [0, 1, 2]);
+    }
+
+    // cargo test --release top_k -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn top_k_against_full_sort() {
+        let items: Vec<String> = (0..500_000)
+            .map(|i| format!("dir{}/file_{}.rs", i % 977, i))
+            .collect();
+        let list: Vec<&str> = items.iter().map(String::as_str).collect();
+
+        for pattern in ["", "d1f2", "file_12"] {
+            let mut limited = picker().query(pattern).limit(10);
+            let start = Instant::now();
+            load(&mut limited, &list);
+            let top_k = start.elapsed();
+
+            let mut full = picker().query(pattern);
+            let start = Instant::now();
+            load(&mut full, &list);
+            full.matches.truncate(10);
+            let full_sort = start.elapsed();
+
+            assert_eq!(limited.matches, full.matches);
+            println!(
+                "{:>8}: top-k {:>10?}, full sort {:>10?}",
+                format!("{:?}", pattern),
+                top_k,
+                full_sort
+            );
+        }
+    }
+
+    #[test]
+    fn chips_show_the_terms_as_matched() {
+        let mut fz = picker().query_chips(true);
+        load(&mut fz, FRUITS);
+        type_text(&mut fz, "foo  !test 'exact ^src rs$ !");
+        assert!(output(&fz).contains("[foo] [!test] ['exact] [^src] [rs$]"));
+
+        let config = MatcherConfig::default();
+        let matches = |item, pattern| score_item(&config, item, pattern).is_some();
+        assert!(matches("foo", "'foo"));
+        assert!(!matches("f_o_o", "'foo"));
+        assert!(matches("src/main.rs", "^src"));
+        assert!(!matches("a/src/main.rs", "^src"));
+        assert!(matches("main.rs", "rs$"));
+        assert!(!matches("main.rs.bak", "rs$"));
+        assert!(matches("src/main.rs", "^src rs$ !^test"));
+        assert!(!matches("test/main.rs", "rs$ !^test"));
+        assert!(matches("src/test.rs", "rs$ !^test"));
+        assert_eq!(
+            score_item(&config, "src/main.rs", "^src ain").unwrap().1,
+            [0, 1, 2, 5, 6, 7]
+        );
+        // positions count characters, not bytes
+        assert_eq!(score_item(&config, "ŻÓŁW.rs", "'łw").unwrap().1, [2, 3]);
+        assert_eq!(score_item(&config, "żółw.rs", "rs$").unwrap().1, [5, 6]);
+        assert_eq!(score_item(&config, "żółw.rs", "^żó").unwrap().1, [0, 1]);
+    }
+
+    #[test]
+    fn outcome_iterates_over_the_list() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker();
+        script(
+            &mut fz,
+            &clock,
+            keys(&[KeyCode::Tab, KeyCode::Up, KeyCode::Tab, KeyCode::Enter]),
+        );
+        // the items outlive the outcome
+        let borrowed: Vec<&str> = {
+            let outcome = fz.run(FRUITS).unwrap();
+            outcome.iter().collect()
+        };
+        assert_eq!(borrowed, ["apple", "banana"]);
+
+        script(&mut fz, &clock, keys(&[KeyCode::Enter]));
+        let mut items = fz.run(FRUITS).unwrap().into_iter();
+        assert_eq!(items.next(), Some("apple"));
+        assert_eq!(items.next(), Some("banana"));
+        assert_eq!(items.next(), None);
+    }
+
+    // whether text contains a select graphic rendition (color or attribute)
+    fn has_sgr(text: &str) -> bool {
+        text.split("\x1b[").skip(1).any(|rest| {
+            let end = rest.find(|c: char| !c.is_ascii_digit() && c != ';');
+            matches!(end.map(|end| &rest[end..end + 1]), Some("m"))
+        })
+    }
+
+    // everything drawn while picking an item
+    fn drawn_with(color: ColorChoice) -> String {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().color(color);
+        let mut events = typing("an");
+        events.extend(keys(&[KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+        fz.select(FRUITS).unwrap();
+        output(&fz)
+    }
+
+    #[test]
+    fn no_color_sequences_unless_enabled() {
+        assert!(has_sgr(&drawn_with(ColorChoice::Always)));
+        assert!(!has_sgr(&drawn_with(ColorChoice::Never)));
+
+        let no_color = Some(OsStr::new("1"));
+        assert!(!style::auto_enabled(no_color, Some("xterm")));
+        assert!(!style::auto_enabled(None, Some("dumb")));
+        // an empty NO_COLOR doesn't count
+        assert!(style::auto_enabled(Some(OsStr::new("")), Some("xterm")));
+        assert!(style::auto_enabled(None, None));
+    }
+
+    #[test]
+    fn placeholder_vanishes_after_typing() {
+        let mut fz = picker().placeholder("Search...");
+        load(&mut fz, FRUITS);
+        fz.redraw().unwrap();
+        assert!(output(&fz).contains("Search..."));
+
+        fz.writer.inner.clear();
+        type_text(&mut fz, "a");
+        assert!(!output(&fz).contains("Search..."));
+        assert_eq!(fz.pattern, "a");
+
+        // back when the pattern is empty again
+        press(&mut fz, KeyCode::Backspace, KeyModifiers::NONE);
+        assert!(output(&fz).contains("Search..."));
+        assert_eq!(fz.cursor, 0);
+    }
+
+    #[test]
+    fn accept_best_match_ignores_the_highlight() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().accept_best_match(true);
+        let mut events = typing("an");
+        events.extend(keys(&[KeyCode::Up, KeyCode::Enter]));
+        script(&mut fz, &clock, events);
+        assert_eq!(
+            fz.select(&["banana", "mango"]).unwrap().as_ref(),
+            ["banana"]
+        );
+        assert_eq!(highlighted(&fz), "mango");
+
+        // selections are still accepted
+        let mut fz = picker().accept_best_match(true);
+        script(
+            &mut fz,
+            &clock,
+            keys(&[KeyCode::Up, KeyCode::Tab, KeyCode::Enter]),
+        );
+        assert_eq!(fz.select(FRUITS).unwrap().as_ref(), ["banana"]);
+    }
+
+    // the column and row that text was first drawn at, if it was drawn right
+    // after moving the cursor
+    fn drawn_at(output: &str, text: &str) -> Option<(u16, u16)> {
+        let before = &output[..output.find(text)?];
+        let moved = before.strip_suffix('H')?;
+        let (row, column) = moved[moved.rfind("\x1b[")? + 2..].split_once(';')?;
+        Some((
+            column.parse::<u16>().ok()? - 1,
+            row.parse::<u16>().ok()? - 1,
+        ))
+    }
+
+    #[test]
+    fn two_column_aligns_the_values() {
+        const PAIRS: &[&str] = &["a: one", "longer: two", "mid:   three"];
+        let mut fz = picker().two_column(':');
+        load(&mut fz, PAIRS);
+        fz.redraw().unwrap();
+        let output = output(&fz);
+
+        let columns: Vec<Option<u16>> = ["one", "two", "three"]
+            .iter()
+            .map(|value| drawn_at(&output, value).map(|(column, _)| column))
+            .collect();
+        // after the markers, the widest key and a space
+        let expected = fz.markers.indent() + "longer".len() as u16 + 1;
+        assert_eq!(columns, [Some(expected); 3]);
+    }
+
+    #[test]
+    fn single_mode_ignores_tab_and_draws_no_markers() {
+        let mut fz = picker().multi(false);
+        load(&mut fz, FRUITS);
+        assert_eq!(press(&mut fz, KeyCode::Tab, KeyModifiers::NONE), None);
+        assert_eq!(press(&mut fz, KeyCode::BackTab, KeyModifiers::SHIFT), None);
+        press(&mut fz, KeyCode::Char('a'), KeyModifiers::CONTROL);
+        assert!(fz.selected.is_empty());
+        assert_eq!(fz.pattern, "");
+
+        // not even for selections restored from elsewhere
+        fz.selected = vec![0, 1].into();
+        fz.redraw().unwrap();
+        assert!(!output(&fz).contains('*'));
+        assert_eq!(fz.accepted(Exit::Accept).unwrap().1, [0]);
+
+        let mut fz = picker();
+        load(&mut fz, FRUITS);
+        fz.selected = vec![0, 1].into();
+        fz.redraw().unwrap();
+        assert!(output(&fz).contains('*'));
+    }
+
+    #[test]
+    fn highlight_position_is_where_the_row_was_drawn() {
+        // the second best match is above the best one by default
+        for (layout, worse) in [
+            (Layout::Default, KeyCode::Up),
+            (Layout::Reverse, KeyCode::Down),
+        ] {
+            let clock = Rc::new(MockClock::new());
+            let mut fz = picker().layout(layout);
+            script(&mut fz, &clock, keys(&[worse, KeyCode::Enter]));
+            let outcome = fz.run(FRUITS).unwrap();
+            assert_eq!(outcome.selected, ["banana"]);
+            assert_eq!(outcome.highlight_position, drawn_at(&output(&fz), "banana"));
+        }
+    }
+
+    #[test]
+    fn path_mode_matches_segment_by_segment() {
+        const PATHS: &[&str] = &[
+            "src/main.rs",
+            "main/src.rs",
+            "src/lib/mod.rs",
+            "docs/src/main.md",
+        ];
+        let matched = |path_mode, pattern| {
+            let mut fz = picker().path_mode(path_mode);
+            load(&mut fz, PATHS);
+            type_text(&mut fz, pattern);
+            let mut matched = fz.matches.clone();
+            matched.sort_unstable();
+            matched.into_iter().map(|m| PATHS[m]).collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched(true, "src/ma"), ["src/main.rs"]);
+        assert_eq!(matched(false, "src/ma").len(), 2);
+        assert_eq!(matched(true, "src/l/m"), ["src/lib/mod.rs"]);
+        // a trailing slash leaves the next segment open
+        assert_eq!(matched(true, "docs/"), ["docs/src/main.md"]);
+        // too many segments
+        assert!(matched(true, "src/main/rs").is_empty());
+
+        let config = MatcherConfig {
+            path_mode: true,
+            ..MatcherConfig::default()
+        };
+        assert_eq!(
+            score_item(&config, "src/main.rs", "s/ma").unwrap().1,
+            [0, 4, 5]
+        );
+    }
+
+    #[test]
+    fn alt_e_draws_the_highlighted_item_in_full() {
+        const WIDE: &[&str] = &["0123456789abcdefghijklmnopqrstuvwxyz", "short"];
+        let mut fz = Fz::with_size(Vec::new(), 16, 8);
+        load(&mut fz, WIDE);
+        fz.redraw().unwrap();
+        assert!(!output(&fz).contains("xyz"));
+
+        fz.writer.inner.clear();
+        press(&mut fz, KeyCode::Char('e'), KeyModifiers::ALT);
+        assert!(fz.expanded);
+        let expanded = output(&fz);
+        for part in ["0123456789abcd", "efghijklmnopqr", "stuvwxyz"] {
+            assert!(expanded.contains(part), "{:?} not drawn", part);
+        }
+        // continuation rows blank the marker columns instead of keeping the
+        // markers of the rows drawn before
+        let gutter = " ".repeat(fz.markers.indent() as usize);
+        let second = format!("{}efghijklmnopqr", gutter);
+        assert_eq!(
+            drawn_at(&expanded, &second).map(|(column, _)| column),
+            Some(0)
+        );
+
+        // the next key collapses it
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        assert!(!fz.expanded);
+        assert_eq!(highlighted(&fz), "short");
+    }
+
+    #[test]
+    fn action_keys_fire_without_exiting() {
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&fired);
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let mut fz = picker()
+            .action(ctrl('e'), "edit")
+            .action(ctrl('o'), "open")
+            .on_action(move |action, item| {
+                log.borrow_mut().push((action.to_owned(), item.to_owned()))
+            });
+        load(&mut fz, FRUITS);
+
+        assert_eq!(
+            press(&mut fz, KeyCode::Char('e'), KeyModifiers::CONTROL),
+            None
+        );
+        press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(
+            press(&mut fz, KeyCode::Char('o'), KeyModifiers::CONTROL),
+            None
+        );
+        assert_eq!(
+            *fired.borrow(),
+            [
+                ("edit".to_owned(), "apple".to_owned()),
+                ("open".to_owned(), "banana".to_owned())
+            ]
+        );
+        assert_eq!(fz.pattern, "");
+        assert_eq!(
+            press(&mut fz, KeyCode::Enter, KeyModifiers::NONE),
+            Some(Exit::Accept)
+        );
+    }
+
+    #[test]
+    fn full_redraw_clears_from_the_top_in_both_layouts() {
+        for layout in [Layout::Default, Layout::Reverse] {
+            // 4 rows at the edge of the screen with the prompt
+            let mut fz = picker().layout(layout).list_rows(3);
+            fz.fit_height();
+            load(&mut fz, FRUITS);
+            fz.redraw().unwrap();
+            let clear = format!("\x1b[{};1H\x1b[J", fz.top + 1);
+            assert!(output(&fz).starts_with(&clear));
+
+            // narrowing to no matches draws everything again
+            fz.writer.inner.clear();
+            type_text(&mut fz, "xyz");
+            assert!(output(&fz).starts_with(&clear));
+        }
+    }
+
+    #[test]
+    fn rank_orders_ties_like_the_picker() {
+        const TIED: &[&str] = &["ab_ab", "xyz", "ab", "a/b", "ab"];
+        let config = MatcherConfig::default();
+        let ranked = rank(&config, TIED, "ab");
+        let order: Vec<&str> = ranked.iter().map(|(item, _, _)| *item).collect();
+        assert_eq!(order, ["a/b", "ab", "ab", "ab_ab"]);
+        assert!(ranked.windows(2).all(|pair| pair[0].1 == pair[1].1));
+        assert_eq!(ranked[0].2, [0, 2]);
+
+        let mut fz = picker();
+        load(&mut fz, TIED);
+        type_text(&mut fz, "ab");
+        assert_eq!(fz.matches, [3, 2, 4, 0]);
+        assert_eq!(
+            fz.scores,
+            ranked
+                .iter()
+                .map(|(_, score, _)| *score)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn matcher_is_built_once() {
+        let built = || matcher::BUILT.with(Cell::get);
+        let mut fz = picker();
+        assert_eq!(built(), 1);
+        load(&mut fz, FRUITS);
+        type_text(&mut fz, "ana");
+        press(&mut fz, KeyCode::Backspace, KeyModifiers::NONE);
+        fz.redraw().unwrap();
+        assert_eq!(built(), 1);
+
+        // changing the case mode needs a new one
+        press(&mut fz, KeyCode::Char('c'), KeyModifiers::ALT);
+        assert_eq!(built(), 2);
+    }
+
+    #[test]
+    fn narrowing_equals_a_full_rescore() {
+        const PATHS: &[&str] = &[
+            "src/main.rs",
+            "src/lib.rs",
+            "src/matcher.rs",
+            "examples/args.rs",
+            "Cargo.toml",
+            "README.md",
+            "main/src.rs",
+            "tests/smart_case.rs",
+        ];
+        for pattern in ["srcma", "s r", "^src rs", "'ma s", "M"] {
+            let mut fz = picker();
+            load(&mut fz, PATHS);
+            // typing narrows the matches, deleting scores all items again
+            let edits = pattern
+                .chars()
+                .map(Some)
+                .chain(pattern.chars().map(|_| None));
+            for edit in edits {
+                match edit {
+                    Some(c) => type_text(&mut fz, &c.to_string()),
+                    None => {
+                        press(&mut fz, KeyCode::Backspace, KeyModifiers::NONE);
+                    }
+                }
+                let mut full = picker();
+                full.pattern = fz.pattern.clone();
+                load(&mut full, PATHS);
+                assert_eq!(
+                    (&fz.matches, &fz.scores),
+                    (&full.matches, &full.scores),
+                    "{:?}",
+                    fz.pattern
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn each_tiebreak_orders_equal_scores() {
+        let ordered = |tiebreak, list: &'static [&'static str], pattern: &str| {
+            let mut fz = picker().tiebreak(tiebreak);
+            load(&mut fz, list);
+            type_text(&mut fz, pattern);
+            fz.matches
+        };
+
+        const WORDS: &[&str] = &["bb", "ccc", "a", "zz"];
+        assert_eq!(ordered(Tiebreak::Alpha, WORDS, ""), [2, 0, 1, 3]);
+        assert_eq!(ordered(Tiebreak::Length, WORDS, ""), [2, 0, 3, 1]);
+        assert_eq!(ordered(Tiebreak::Index, WORDS, ""), [0, 1, 2, 3]);
+
+        // all score the same for ab
+        const TIED: &[&str] = &["ab_ab", "ab", "a/b"];
+        assert_eq!(ordered(Tiebreak::Alpha, TIED, "ab"), [2, 1, 0]);
+        assert_eq!(ordered(Tiebreak::Length, TIED, "ab"), [1, 2, 0]);
+        assert_eq!(ordered(Tiebreak::Index, TIED, "ab"), [0, 1, 2]);
+    }
+
+    #[test]
+    fn tiny_terminals_redraw_without_panicking() {
+        let sizes = [(1, 1), (2, 2), (1, 3), (3, 1)];
+        for ((width, height), border) in
+            sizes.iter().flat_map(|&size| [(size, false), (size, true)])
+        {
+            let mut fz = Fz::with_size(Vec::new(), width, height)
+                .prompt("> ")
+                .placeholder("Search...")
+                .border(border)
+                .scrollbar(true)
+                .show_scores(true);
+            fz.fit_height();
+            load(&mut fz, FRUITS);
+            fz.redraw().unwrap();
+            type_text(&mut fz, "a");
+            press(&mut fz, KeyCode::Up, KeyModifiers::NONE);
+            press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+            fz.handle_event(Ok(Event::Resize(width, height)), Instant::now())
+                .unwrap();
+            fz.flush_changes().unwrap();
+        }
+    }
+
+    #[test]
+    fn negated_and_alternative_terms_combine() {
+        const ITEMS: &[&str] = &["foo baz", "foo bar baz", "foo qux", "baz", "foo"];
+        let matched = |pattern| {
+            let mut fz = picker();
+            load(&mut fz, ITEMS);
+            type_text(&mut fz, pattern);
+            let mut matched = fz.matches.clone();
+            matched.sort_unstable();
+            matched.into_iter().map(|m| ITEMS[m]).collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched("foo !bar baz"), ["foo baz"]);
+        assert_eq!(matched("foo baz !bar"), ["foo baz"]);
+        // | binds tighter than the whitespace joining groups
+        assert_eq!(matched("foo !bar baz | qux"), ["foo baz", "foo qux"]);
+        assert_eq!(
+            matched("baz | qux"),
+            ["foo baz", "foo bar baz", "foo qux", "baz"]
+        );
+        // a dangling | or lone ! changes nothing
+        assert_eq!(matched("| foo !bar baz ! |"), ["foo baz"]);
+
+        let mut fz = picker().query_chips(true);
+        load(&mut fz, ITEMS);
+        type_text(&mut fz, "foo !bar baz | 'qux");
+        assert!(output(&fz).contains("[foo] [!bar] [baz | 'qux]"));
+        assert_eq!(
+            parse_query("a | b c")
+                .iter()
+                .map(Vec::len)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+    }
+
+    // whether the terminal was restored after picking ended
+    fn restored(fz: &Fz<'_, Vec<u8>>, replay: &Replay) -> bool {
+        let output = output(fz);
+        !replay.raw.get()
+            && output.ends_with("\x1b[?7h")
+            && output.contains("\x1b[?1049l")
+            && output.find("\x1b[?1049h") < output.find("\x1b[?1049l")
+    }
+
+    #[test]
+    fn errors_and_panics_restore_the_terminal() {
+        let clock = Rc::new(MockClock::new());
+        // reading fails once the typed events run out
+        let mut fz = picker();
+        let replay = script(&mut fz, &clock, typing("an"));
+        assert!(fz.run(FRUITS).is_err());
+        assert!(restored(&fz, &replay));
+
+        let mut fz = picker()
+            .action(
+                KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
+                "explode",
+            )
+            .on_action(|_, _| panic!("callback failed"));
+        let mut events = typing("an");
+        events.push(Event::Key(KeyEvent::new(
+            KeyCode::Char('x'),
+            KeyModifiers::CONTROL,
+        )));
+        let replay = script(&mut fz, &clock, events);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fz.run(FRUITS)));
+        assert!(result.is_err());
+        assert!(restored(&fz, &replay));
+    }
+
+    #[test]
+    fn selections_join_for_output() {
+        let selected = Cow::Borrowed(&["a file", "b\nc"][..]);
+        assert_eq!(
+            join_records(&selected, RecordDelimiter::Newline),
+            "a file\nb\nc\n"
+        );
+        assert_eq!(
+            join_records(&selected, RecordDelimiter::Null),
+            "a file\0b\nc\0"
+        );
+        assert_eq!(join_separated(&selected, ","), "a file,b\nc");
+        assert_eq!(join_separated(&["one".to_owned()], ", "), "one");
+        assert_eq!(join_separated::<&str>(&[], ","), "");
+    }
+
+    #[test]
+    fn auto_accept_waits_while_items_are_selected() {
+        let mut fz = picker().auto_accept_single(true);
+        load(&mut fz, FRUITS);
+        assert_eq!(
+            press(&mut fz, KeyCode::Char('c'), KeyModifiers::NONE),
+            Some(Exit::AcceptHighlight)
+        );
+        assert_eq!(highlighted(&fz), "cherry");
+
+        let mut fz = picker().auto_accept_single(true);
+        load(&mut fz, FRUITS);
+        press(&mut fz, KeyCode::Tab, KeyModifiers::NONE);
+        type_text(&mut fz, "c");
+        assert_eq!(fz.matches.len(), 1);
+        let exit = press(&mut fz, KeyCode::Enter, KeyModifiers::NONE).unwrap();
+        assert_eq!(fz.accepted(exit).unwrap().1, [0]);
+    }
+
+    #[test]
+    fn chords_are_timed_by_when_keys_arrive() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().vim_mode(true).clock(Rc::clone(&clock));
+        let (no, quick, slow) = (
+            Duration::ZERO,
+            Duration::from_millis(100),
+            Duration::from_secs(5),
+        );
+        let events = [
+            (no, KeyCode::Esc),
+            (no, KeyCode::Char('k')),
+            (no, KeyCode::Char('k')),
+            (slow, KeyCode::Char('g')),
+            (quick, KeyCode::Char('g')),
+            (no, KeyCode::Enter),
+        ];
+        let events = events
+            .iter()
+            .map(|&(wait, code)| (wait, Event::Key(KeyEvent::new(code, KeyModifiers::NONE))))
+            .collect();
+        let replay = script_paced(&mut fz, &clock, events);
+        assert_eq!(fz.select(FRUITS).unwrap().as_ref(), ["apple"]);
+        // read without polling, waiting for each key
+        assert_eq!(replay.polls.get(), 0);
+    }
+
+    #[test]
+    fn failed_setup_undoes_only_what_was_done() {
+        let clock = Rc::new(MockClock::new());
+        let mut fz = picker().inline(5).mouse(true);
+        let replay = install(&mut fz, &clock, Vec::new(), None);
+        assert!(fz.run(FRUITS).is_err());
+        // raw mode is left, but nothing was drawn so nothing is cleared
+        assert!(!replay.raw.get());
+        assert_eq!(output(&fz), "");
+    }
+
+    #[test]
+    #[cfg(feature = "clipboard")]
+    fn copy_key_can_be_rebound() {
+        let ctrl_y = KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL);
+        let mut fz = picker().copy_key(ctrl_y);
+        load(&mut fz, FRUITS);
+        type_text(&mut fz, "an");
+
+        fz.writer.inner.clear();
+        press(&mut fz, KeyCode::Char('y'), KeyModifiers::ALT);
+        assert!(!output(&fz).contains("\x1b]52;"));
+
+        press(&mut fz, KeyCode::Char('y'), KeyModifiers::CONTROL);
+        assert!(output(&fz).contains("\x1b]52;c;YW4=\x07"));
+        assert!(output(&fz).contains("query copied"));
     }
 }
@@ -0,0 +1,358 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{
+    query::{parse_query, Term, TermKind},
+    Tiebreak,
+};
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    fmt::{Display, Formatter},
+};
+
+/// Scores `item` against `pattern` exactly like the picker does, returning the
+/// score and the character indices of `item` that matched.
+///
+/// Higher scores are better, the picker ranks matches with the best score
+/// first. `None` means that `item` doesn't match.
+///
+/// A matcher is built for every call, [`rank`] builds one for all items like
+/// the picker, which builds one only when the matcher options change.
+pub fn score_item(config: &MatcherConfig, item: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    config.score(&config.matcher(), item, pattern, true)
+}
+
+/// Ranks `items` against `pattern` like the picker does with its default
+/// options, returning the matching items with their scores and the character
+/// indices that matched.
+///
+/// Matches are ordered best first, equal scores alphabetically
+/// ([`Tiebreak::Alpha`]) and equal items by their position in `items`. The
+/// options of the picker changing the order, like [`Fz::tiebreak`] or
+/// [`Fz::exact_match_boost`], aren't applied.
+///
+/// [`Fz::tiebreak`]: crate::Fz::tiebreak
+/// [`Fz::exact_match_boost`]: crate::Fz::exact_match_boost
+pub fn rank<'a>(
+    config: &MatcherConfig,
+    items: &[&'a str],
+    pattern: &str,
+) -> Vec<(&'a str, i64, Vec<usize>)> {
+    let matcher = config.matcher();
+    let mut ranked: Vec<((usize, bool, i64), Vec<usize>)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let (score, indices) = config.score(&matcher, item, pattern, true)?;
+            Some(((i, false, score), indices))
+        })
+        .collect();
+
+    let tiebreak = |a: usize, b: usize| Tiebreak::Alpha.compare(items[a], items[b]).then(a.cmp(&b));
+    ranked.sort_unstable_by(|(a, _), (b, _)| by_rank(a, b, tiebreak));
+
+    ranked
+        .into_iter()
+        .map(|((i, _, score), indices)| (items[i], score, indices))
+        .collect()
+}
+
+// orders scored items (position in the list, whether it's a boosted exact
+// match and the score) best first, exact matches first and equal scores by
+// tiebreak, which has to tell apart different positions
+pub(crate) fn by_rank(
+    (a, a_exact, a_score): &(usize, bool, i64),
+    (b, b_exact, b_score): &(usize, bool, i64),
+    tiebreak: impl Fn(usize, usize) -> Ordering,
+) -> Ordering {
+    b_exact
+        .cmp(a_exact)
+        .then(b_score.cmp(a_score))
+        .then_with(|| tiebreak(*a, *b))
+}
+
+/// Scores items in place of the built-in matcher, see
+/// [`Fz::matcher`](crate::Fz::matcher).
+pub trait Matcher {
+    /// Scores `item` against the non-empty `pattern`, higher scores are
+    /// better. `None` means that `item` doesn't match.
+    fn score(&self, item: &str, pattern: &str) -> Option<i64>;
+
+    /// Character indices of a matching `item` drawn as matched, none by
+    /// default.
+    fn indices(&self, item: &str, pattern: &str) -> Vec<usize> {
+        let _ = (item, pattern);
+        Vec::new()
+    }
+}
+
+/// Options affecting which items match and how they are scored.
+///
+/// Patterns are split at whitespace into terms that all have to match, the
+/// score of an item is the sum of the scores of the terms. `foo bar` matches
+/// both `foobar` and `bar.foo`.
+///
+/// A term written as `'term` has to appear as a substring, `^term` at the
+/// start and `term$` at the end of the item. A term starting with `!`
+/// excludes items containing the rest of the term (as a substring unless it's
+/// anchored), and a `|` between terms (separated by whitespace) makes either
+/// term enough. `|` binds tighter than the whitespace joining terms, so
+/// `foo !bar baz | qux` matches items matching `foo`, not containing `bar`
+/// and matching `baz` or `qux`, see [`parse_query`](crate::parse_query).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatcherConfig {
+    pub case_mode: CaseMode,
+    pub match_mode: MatchMode,
+    /// Match patterns containing `/` segment by segment, so each segment of
+    /// the pattern has to match the segment of the item at the same position:
+    /// `src/ma` matches `src/main.rs` but not `main/src.rs`.
+    pub path_mode: bool,
+}
+
+// number of matchers built on this thread, for tests
+#[cfg(test)]
+thread_local! {
+    pub(crate) static BUILT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+impl MatcherConfig {
+    pub(crate) fn matcher(&self) -> SkimMatcherV2 {
+        #[cfg(test)]
+        BUILT.with(|built| built.set(built.get() + 1));
+        match self.case_mode {
+            CaseMode::Smart => SkimMatcherV2::default().smart_case(),
+            CaseMode::Insensitive => SkimMatcherV2::default().ignore_case(),
+            CaseMode::Sensitive => SkimMatcherV2::default().respect_case(),
+        }
+    }
+
+    // whether the case mode makes pattern case sensitive
+    fn case_sensitive(&self, pattern: &str) -> bool {
+        match self.case_mode {
+            CaseMode::Smart => pattern.chars().any(char::is_uppercase),
+            CaseMode::Insensitive => false,
+            CaseMode::Sensitive => true,
+        }
+    }
+
+    // whether item equals pattern under the case mode
+    pub(crate) fn is_exact(&self, item: &str, pattern: &str) -> bool {
+        match self.case_sensitive(pattern) {
+            true => item == pattern,
+            false => item.to_lowercase() == pattern.to_lowercase(),
+        }
+    }
+
+    // scores a single item with a matcher built by self.matcher(), indices of
+    // matched characters are only computed if requested
+    pub(crate) fn score(
+        &self,
+        matcher: &SkimMatcherV2,
+        item: &str,
+        pattern: &str,
+        indices: bool,
+    ) -> Option<(i64, Vec<usize>)> {
+        // match multi-line items as a single line, keeping character indices
+        let folded;
+        let item = match item.contains('\n') {
+            true => {
+                folded = item.replace('\n', " ");
+                &folded
+            }
+            false => item,
+        };
+
+        // every group has to match, summing the scores
+        let mut total = 0;
+        let mut matched = Vec::new();
+        for group in parse_query(pattern) {
+            // the best scoring alternative of the group
+            let (score, term_indices) = group
+                .into_iter()
+                .filter_map(|term| self.score_term(matcher, item, &term, indices))
+                .max_by_key(|(score, _)| *score)?;
+            total += score;
+            matched.extend(term_indices);
+        }
+        // terms may match the same characters
+        matched.sort_unstable();
+        matched.dedup();
+
+        Some((total, matched))
+    }
+
+    // scores a single term, negated terms match items not containing them
+    // and add nothing to the score
+    fn score_term(
+        &self,
+        matcher: &SkimMatcherV2,
+        item: &str,
+        term: &Term<'_>,
+        indices: bool,
+    ) -> Option<(i64, Vec<usize>)> {
+        let text = term.text;
+        if term.negated {
+            return match self.locate(item, term) {
+                Some(_) => None,
+                None => Some((0, Vec::new())),
+            };
+        }
+        match term.kind {
+            TermKind::Fuzzy if self.path_mode && text.contains('/') => {
+                self.score_segments(matcher, item, text, indices)
+            }
+            TermKind::Fuzzy => self.score_text(matcher, item, text, indices),
+            // scored like the text matching itself, so all items containing
+            // the term score the same for it
+            _ => {
+                let start = self.locate(item, term)?;
+                let score = matcher.fuzzy_match(text, text).unwrap_or(0);
+                match indices {
+                    true => Some((score, (start..start + text.chars().count()).collect())),
+                    false => Some((score, Vec::new())),
+                }
+            }
+        }
+    }
+
+    // character index where the text of term appears in item as required by
+    // its kind, anywhere as a substring for fuzzy terms
+    fn locate(&self, item: &str, term: &Term<'_>) -> Option<usize> {
+        let case_sensitive = self.case_sensitive(term.text);
+        let len = term.text.chars().count();
+        match term.kind {
+            TermKind::Fuzzy | TermKind::Exact => find(item, term.text, case_sensitive),
+            TermKind::Prefix => {
+                let end = item.char_indices().nth(len).map_or(item.len(), |(i, _)| i);
+                find(&item[..end], term.text, case_sensitive)
+            }
+            TermKind::Suffix => {
+                let skipped = item.chars().count().checked_sub(len)?;
+                let start = item
+                    .char_indices()
+                    .nth(skipped)
+                    .map_or(item.len(), |(i, _)| i);
+                find(&item[start..], term.text, case_sensitive).map(|_| skipped)
+            }
+        }
+    }
+
+    // scores text under the match mode
+    fn score_text(
+        &self,
+        matcher: &SkimMatcherV2,
+        text: &str,
+        pattern: &str,
+        indices: bool,
+    ) -> Option<(i64, Vec<usize>)> {
+        match self.match_mode {
+            MatchMode::Fuzzy => matcher.fuzzy(text, pattern, indices),
+            MatchMode::Exact => {
+                let start = find(text, pattern, self.case_sensitive(pattern))?;
+                // earlier occurrences are better
+                let score = -(start as i64);
+                match indices {
+                    true => Some((score, (start..start + pattern.chars().count()).collect())),
+                    false => Some((score, Vec::new())),
+                }
+            }
+        }
+    }
+
+    // matches each segment of pattern against the segment of item at the same
+    // position, summing the scores
+    fn score_segments(
+        &self,
+        matcher: &SkimMatcherV2,
+        item: &str,
+        pattern: &str,
+        indices: bool,
+    ) -> Option<(i64, Vec<usize>)> {
+        let mut segments = item.split('/');
+        let mut total = 0;
+        let mut matched = Vec::new();
+        // character index of the current segment in item
+        let mut start = 0;
+
+        for part in pattern.split('/') {
+            let segment = segments.next()?;
+            // empty parts (like after a trailing slash) match any segment
+            if !part.is_empty() {
+                let (score, part_indices) = self.score_text(matcher, segment, part, indices)?;
+                total += score;
+                matched.extend(part_indices.into_iter().map(|i| start + i));
+            }
+            start += segment.chars().count() + 1;
+        }
+
+        Some((total, matched))
+    }
+}
+
+// character index of the first occurrence of pattern in text
+//
+// runs for every item on every keystroke, so nothing is allocated
+fn find(text: &str, pattern: &str, case_sensitive: bool) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let eq = |a: char, b: char| match case_sensitive {
+        true => a == b,
+        false => a.to_lowercase().eq(b.to_lowercase()),
+    };
+
+    text.char_indices().position(|(start, _)| {
+        let mut rest = text[start..].chars();
+        pattern
+            .chars()
+            .all(|p| rest.next().map_or(false, |t| eq(t, p)))
+    })
+}
+
+/// How the pattern is matched against items.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The characters of the pattern have to appear in order, scored by
+    /// SkimMatcherV2.
+    #[default]
+    Fuzzy,
+    /// The pattern has to appear as a substring, earlier occurrences rank
+    /// higher. Case sensitivity follows the case mode.
+    Exact,
+}
+
+/// Case sensitivity of matching, cycled at runtime with ALT-C.
+///
+/// Only affects non-empty patterns, the empty pattern matches every item.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    /// Case sensitive only if the pattern contains an uppercase character.
+    #[default]
+    Smart,
+    /// Always case insensitive.
+    Insensitive,
+    /// Always case sensitive.
+    Sensitive,
+}
+
+impl CaseMode {
+    // the mode following self when cycling
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Self::Smart => Self::Insensitive,
+            Self::Insensitive => Self::Sensitive,
+            Self::Sensitive => Self::Smart,
+        }
+    }
+}
+
+impl Display for CaseMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Smart => "smart-case",
+            Self::Insensitive => "ignore-case",
+            Self::Sensitive => "respect-case",
+        })
+    }
+}
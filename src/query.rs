@@ -0,0 +1,94 @@
+use std::fmt::{self, Display, Formatter};
+
+/// How a [`Term`] of a query is matched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TermKind {
+    /// Fuzzy match, the default.
+    Fuzzy,
+    /// Match a substring, written as `'term`.
+    Exact,
+    /// Match the start of the item, written as `^term`.
+    Prefix,
+    /// Match the end of the item, written as `term$`.
+    Suffix,
+}
+
+/// A whitespace separated part of a query, see [`parse_query`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Term<'a> {
+    /// The text to match without the prefix and suffix characters.
+    pub text: &'a str,
+    pub kind: TermKind,
+    /// Written as `!term`, items matching the term are excluded.
+    pub negated: bool,
+}
+
+impl Display for Term<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("!")?;
+        }
+        match self.kind {
+            TermKind::Fuzzy => f.write_str(self.text),
+            TermKind::Exact => write!(f, "'{}", self.text),
+            TermKind::Prefix => write!(f, "^{}", self.text),
+            TermKind::Suffix => write!(f, "{}$", self.text),
+        }
+    }
+}
+
+/// Splits a query into groups of terms that all have to match, the terms of a
+/// group are alternatives joined by `|` of which any one is enough. Terms left
+/// empty after removing the prefix and suffix characters (like a lone `!`)
+/// and a dangling `|` are skipped.
+///
+/// `foo !bar 'baz ^qux quux$` is the fuzzy term `foo`, the negated term `bar`,
+/// the exact term `baz`, the prefix `qux` and the suffix `quux`, each in a
+/// group of its own. `foo | bar baz` is the group of the alternatives `foo`
+/// and `bar`, followed by the group of `baz`.
+pub fn parse_query(query: &str) -> Vec<Vec<Term<'_>>> {
+    let mut groups: Vec<Vec<Term<'_>>> = Vec::new();
+    let mut joined = false;
+    for word in query.split_whitespace() {
+        if word == "|" {
+            joined = !groups.is_empty();
+            continue;
+        }
+        let term = match parse_term(word) {
+            Some(term) => term,
+            None => continue,
+        };
+        match groups.last_mut() {
+            Some(group) if joined => group.push(term),
+            _ => groups.push(vec![term]),
+        }
+        joined = false;
+    }
+    groups
+}
+
+// parses a single whitespace separated term, None if it's left empty
+fn parse_term(term: &str) -> Option<Term<'_>> {
+    let (negated, term) = match term.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, term),
+    };
+    let (kind, text) = if let Some(rest) = term.strip_prefix('\'') {
+        (TermKind::Exact, rest)
+    } else if let Some(rest) = term.strip_prefix('^') {
+        (TermKind::Prefix, rest)
+    } else if let Some(rest) = term.strip_suffix('$') {
+        (TermKind::Suffix, rest)
+    } else {
+        (TermKind::Fuzzy, term)
+    };
+
+    match text.is_empty() {
+        true => None,
+        false => Some(Term {
+            text,
+            kind,
+            negated,
+        }),
+    }
+}
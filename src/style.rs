@@ -0,0 +1,108 @@
+use crossterm::{
+    style::{Color, SetBackgroundColor, SetForegroundColor},
+    QueueableCommand,
+};
+
+use crate::Result;
+use std::{env, ffi::OsStr, io::Write};
+use unicode_width::UnicodeWidthStr;
+
+/// Whether colors and text attributes are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Draw styles unless `NO_COLOR` is set or `TERM` is `dumb`.
+    #[default]
+    Auto,
+    /// Always draw styles.
+    Always,
+    /// Never draw styles.
+    Never,
+}
+
+impl ColorChoice {
+    // whether styles should be drawn, checking the environment for Auto
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => auto_enabled(
+                env::var_os("NO_COLOR").as_deref(),
+                env::var("TERM").ok().as_deref(),
+            ),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// whether Auto draws styles given the values of `NO_COLOR` and `TERM`
+pub(crate) fn auto_enabled(no_color: Option<&OsStr>, term: Option<&str>) -> bool {
+    let no_color = no_color.map_or(false, |v| !v.is_empty());
+    let dumb = term == Some("dumb");
+    !no_color && !dumb
+}
+
+/// Colors of a part of the picker, `None` keeps the color of the terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+impl Style {
+    // sets the colors of the following text, resetting unset colors
+    pub(crate) fn apply<W: Write>(self, writer: &mut W) -> Result<()> {
+        writer
+            .queue(SetForegroundColor(self.foreground.unwrap_or(Color::Reset)))?
+            .queue(SetBackgroundColor(self.background.unwrap_or(Color::Reset)))?;
+        Ok(())
+    }
+}
+
+/// Colors of the parts of the picker, see [`Fz::theme`](crate::Fz::theme).
+///
+/// The default theme keeps the colors of the terminal everywhere.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Theme {
+    /// Rows of items that aren't highlighted.
+    pub normal: Style,
+    /// The highlighted row and its pointer marker.
+    pub current: Style,
+    /// The selection markers.
+    pub marker: Style,
+    /// Characters matched by the pattern, drawn in bold.
+    pub matched: Style,
+}
+
+/// Characters drawn left of the items, see [`Fz::markers`](crate::Fz::markers).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Markers {
+    /// Drawn left of the highlighted item, `>` by default.
+    pub pointer: String,
+    /// Drawn after the pointer for selected items, `*` by default.
+    pub selected: String,
+    /// Columns from the left edge of the list to the items, 2 by default.
+    /// Widened to fit both markers.
+    pub indent: u16,
+}
+
+impl Default for Markers {
+    fn default() -> Self {
+        Markers {
+            pointer: String::from(">"),
+            selected: String::from("*"),
+            indent: 2,
+        }
+    }
+}
+
+impl Markers {
+    // column of the items relative to the left edge of the list
+    pub(crate) fn indent(&self) -> u16 {
+        self.indent
+            .max((self.pointer.width() + self.selected.width()) as u16)
+    }
+
+    // column of the selection marker relative to the left edge of the list
+    pub(crate) fn selected_column(&self) -> u16 {
+        self.pointer.width() as u16
+    }
+}
@@ -0,0 +1,210 @@
+use crossterm::{
+    style::{Attribute, ResetColor, SetAttribute},
+    QueueableCommand,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{Result, Style};
+use std::{borrow::Cow, io::Write, iter};
+
+// columns dimmed by TruncationStyle::Fade
+const FADE_WIDTH: usize = 3;
+
+/// How items wider than the list are cut.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TruncationStyle {
+    /// End the visible part with `..`.
+    #[default]
+    Ellipsis,
+    /// Dim the last few visible columns.
+    Fade,
+    /// Cut without any indicator.
+    Clip,
+}
+
+// splits text into lines of at most width columns, breaking at line breaks and
+// anywhere within lines
+pub(crate) fn wrap(text: &str, width: usize) -> Vec<&str> {
+    let mut wrapped = Vec::new();
+    for mut line in text.lines() {
+        loop {
+            let (head, rest) = split_at_width(line, width);
+            // always make progress, even if a character is wider than width
+            let (head, rest) = match head.is_empty() && !rest.is_empty() {
+                true => rest.split_at(rest.chars().next().map_or(0, char::len_utf8)),
+                false => (head, rest),
+            };
+            wrapped.push(head);
+            if rest.is_empty() {
+                break;
+            }
+            line = rest;
+        }
+    }
+    wrapped
+}
+
+// splits text at the last character boundary fitting in width columns
+pub(crate) fn split_at_width(text: &str, width: usize) -> (&str, &str) {
+    split_visible(text, width, false)
+}
+
+// byte length of the escape sequence (e.g. SGR colors) text starts with, 0 if
+// it doesn't start with a complete one
+fn escape_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    if !bytes.starts_with(b"\x1b[") {
+        return 0;
+    }
+    // parameters up to the final byte
+    match bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b)) {
+        Some(end) => end + 3,
+        None => 0,
+    }
+}
+
+// characters of text with their byte offsets, skipping escape sequences if
+// ansi is set
+pub(crate) fn visible_chars(text: &str, ansi: bool) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut start = 0;
+    iter::from_fn(move || loop {
+        let rest = &text[start..];
+        let skip = match ansi {
+            true => escape_len(rest),
+            false => 0,
+        };
+        if skip > 0 {
+            start += skip;
+            continue;
+        }
+        let c = rest.chars().next()?;
+        let i = start;
+        start += c.len_utf8();
+        return Some((i, c));
+    })
+}
+
+// columns taken by text, escape sequences take none if ansi is set
+pub(crate) fn visible_width(text: &str, ansi: bool) -> usize {
+    visible_chars(text, ansi)
+        .map(|(_, c)| c.width().unwrap_or(0))
+        .sum()
+}
+
+// text without its escape sequences
+pub(crate) fn strip_ansi(text: &str) -> Cow<'_, str> {
+    match text.contains('\x1b') {
+        true => Cow::Owned(visible_chars(text, true).map(|(_, c)| c).collect()),
+        false => Cow::Borrowed(text),
+    }
+}
+
+// like split_at_width, escape sequences take no columns if ansi is set
+fn split_visible(text: &str, width: usize, ansi: bool) -> (&str, &str) {
+    let mut used = 0;
+    for (i, c) in visible_chars(text, ansi) {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return text.split_at(i);
+        }
+    }
+    (text, "")
+}
+
+// writes text at the cursor, truncated to width columns, with the characters
+// at the (sorted) indices in highlight drawn in bold and in the matched colors,
+// and the rest in the row colors. With ansi set escape sequences in text are
+// passed through without taking columns or indices, and reset at the end
+pub(crate) fn write_truncated<W: Write>(
+    writer: &mut W,
+    text: &str,
+    width: usize,
+    (style, ansi): (TruncationStyle, bool),
+    highlight: &[usize],
+    (row, matched): (Style, Style),
+) -> Result<()> {
+    let (visible, rest) = split_visible(text, width, ansi);
+    // escape sequences left over don't make the text any wider
+    let rest = match ansi {
+        true => strip_ansi(rest),
+        false => Cow::Borrowed(rest),
+    };
+
+    // the visible text, padding and end marker, and where fading starts
+    let (visible, padding, end, faded) = match (rest.is_empty(), style) {
+        (true, _) | (false, TruncationStyle::Clip) => (visible, 0, "", None),
+        (false, TruncationStyle::Ellipsis) => {
+            let (visible, _) = split_visible(visible, width.saturating_sub(2), ansi);
+            // pad over a wide character cut in half, so .. ends at the last column
+            let padding = width.saturating_sub(2) - visible_width(visible, ansi);
+            (visible, padding, &".."[..width.min(2)], None)
+        }
+        (false, TruncationStyle::Fade) => {
+            let (normal, _) = split_visible(visible, width.saturating_sub(FADE_WIDTH), ansi);
+            (visible, 0, "", Some(visible_chars(normal, ansi).count()))
+        }
+    };
+
+    // colors are only emitted if a theme sets them
+    let colored = row != Style::default() || matched != Style::default();
+    if colored {
+        row.apply(writer)?;
+    }
+
+    if highlight.is_empty() && faded.is_none() {
+        writer.write_all(visible.as_bytes())?;
+    } else {
+        // (bold, dim) of the previous character
+        let mut current = (false, false);
+        // end of the previous character, escape sequences since are passed on
+        let mut start = 0;
+        for (i, (offset, c)) in visible_chars(visible, ansi).enumerate() {
+            writer.write_all(&visible.as_bytes()[start..offset])?;
+            start = offset + c.len_utf8();
+            let state = (
+                highlight.binary_search(&i).is_ok(),
+                faded.map_or(false, |f| i >= f),
+            );
+            if state != current {
+                if current != (false, false) {
+                    writer.queue(SetAttribute(Attribute::NormalIntensity))?;
+                }
+                if state.0 {
+                    writer.queue(SetAttribute(Attribute::Bold))?;
+                }
+                if state.1 {
+                    writer.queue(SetAttribute(Attribute::Dim))?;
+                }
+                if colored && state.0 != current.0 {
+                    match state.0 {
+                        true => matched.apply(writer)?,
+                        false => row.apply(writer)?,
+                    }
+                }
+                current = state;
+            }
+            write!(writer, "{}", c)?;
+        }
+        writer.write_all(&visible.as_bytes()[start..])?;
+        if current != (false, false) {
+            writer.queue(SetAttribute(Attribute::NormalIntensity))?;
+        }
+        if colored && current.0 {
+            row.apply(writer)?;
+        }
+    }
+    // keep the styles of the text from bleeding into the rest of the row
+    if ansi {
+        writer.queue(SetAttribute(Attribute::Reset))?;
+        if colored {
+            row.apply(writer)?;
+        }
+    }
+    write!(writer, "{:padding$}{}", "", end, padding = padding)?;
+
+    if colored {
+        writer.queue(ResetColor)?;
+    }
+
+    Ok(())
+}